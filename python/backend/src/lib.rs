@@ -0,0 +1,7 @@
+/// Author: Renier Barnard
+/// Chess rules, FEN/PGN parsing and the JSON game protocol types shared by the CLI
+pub mod openings;
+pub mod parsers;
+pub mod session;
+pub mod validation;
+pub mod zobrist;
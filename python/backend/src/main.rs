@@ -1,14 +1,29 @@
-use parsers::{
+use chess::parsers::{
     fen_parser::Gamestate,
     notation::*,
     parse_error::*,
     parse_input::{read_and_parse_input, JsonIn},
     parse_output::*,
-    pgn_parser::PgnGame,
+    pgn_parser::{self, PgnSummary},
+    san::move_to_san,
 };
+use chess::openings::identify;
+use chess::session::{Session, DEFAULT_SEARCH_DEPTH};
+use serde_json::{json, Value};
 use std::env;
-use validation::board_validation::validate_board;
-use validation::possible_moves::{get_game_status, get_legal_moves, is_in_check, GameStatus};
+use std::fmt::Write;
+use std::str::FromStr;
+use std::time::Instant;
+use chess::validation::draw::{material_verdict, MaterialVerdict};
+use chess::validation::evaluation::{mobility, Evaluation};
+use chess::validation::make_move::{classify_move, make_move, MoveKind};
+use chess::validation::perft::{perft, perft_epd_line, BENCH_POSITIONS};
+use chess::validation::possible_moves::{
+    explain_illegal, get_legal_moves, is_fivefold_count, is_in_check, is_quiet, legal_moves_map,
+    CoordMove, GameStatus, PROMOTION_PIECES,
+};
+use chess::validation::exchange::see;
+use chess::validation::search::{find_mate_in_one, search_with};
 
 // Author: Renier Barnard
 // Fixed: Added checkmate/stalemate detection and pawn promotion
@@ -16,10 +31,13 @@ use validation::possible_moves::{get_game_status, get_legal_moves, is_in_check,
 // - Fixed castling rights removal when rook is captured
 // - Fixed en passant reset logic
 // - Added 50-move rule draw detection
-mod parsers;
-mod validation;
 
-fn cli() {
+fn cli(config: &Config) {
+    let mut session = Session::new()
+        .with_evaluation(config.evaluation)
+        .with_search_depth(config.depth)
+        .with_material_report(config.material)
+        .with_interactive_promotion(config.interactive_promotion);
     loop {
         let input: JsonIn = match read_and_parse_input() {
             Ok(input) => input,
@@ -37,375 +55,1096 @@ fn cli() {
             continue;
         }
 
-        let mut message: &str = "";
-        let mut in_check = false;
-        let mut checkmate = false;
-        let mut stalemate = false;
-
-        let mut game: Gamestate = input.state;
-        if input.reason == "start" {
-            message = "valid";
-        } else if input.reason == "move" {
-            let moves: ((u8, u8), (u8, u8)) = match input.moves.split_once('-') {
-                Some((from, to)) => (
-                    chess_notation_to_index(from).expect("Invalid move notation"),
-                    chess_notation_to_index(to).expect("Invalid move notation"),
-                ),
-                None => ((0, 0), (0, 0)),
-            };
-
-            let enpassat: (u8, u8) = game.enpassat.unwrap_or((0, 0));
+        match handle_input(&mut session, input) {
+            Ok(output) => println!("{}", output),
+            Err(e) => e.print_stderr(),
+        }
+    }
+}
 
-            // Calculate legal moves ONCE
-            let legal_moves_before =
-                get_legal_moves(&game.board, enpassat, game.castling, game.player);
+/// Handles a single protocol request against the session and builds the JSON reply
+fn handle_input(session: &mut Session, mut input: JsonIn) -> Result<Value, ParseError> {
+    let mut message: &str = "";
+    let mut in_check = false;
+    let mut checkmate = false;
+    let mut stalemate = false;
+    let mut outcome = Outcome::default();
+    // The legal moves of the position being reported, generated once and shared by the
+    // outcome and the reply
+    let mut legal_moves: Vec<CoordMove> = Vec::new();
 
-            if legal_moves_before.contains(&moves) {
-                let piece: char = game.board[moves.0 .0 as usize][moves.0 .1 as usize];
-                let target: char = game.board[moves.1 .0 as usize][moves.1 .1 as usize];
+    let mut game: Gamestate = input.state;
 
-                game.board[moves.1 .0 as usize][moves.1 .1 as usize] = piece;
-                game.board[moves.0 .0 as usize][moves.0 .1 as usize] = ' ';
+    // A dry run of a move, leaving the session alone
+    if input.reason == "trymove" {
+        return Ok(try_move(&game, &input.moves));
+    }
 
-                // FIX: Reset en passant by default (will be set again if pawn double-move)
-                game.enpassat = None;
+    // The piece for a promotion held back by `--interactive-promotion` turns the pending
+    // move into an ordinary one
+    if input.reason == "promote" {
+        let piece = match input.piece.chars().collect::<Vec<_>>()[..] {
+            [piece] if PROMOTION_PIECES.contains(&piece.to_ascii_lowercase()) => piece,
+            _ => {
+                return Err(ParseError::new(
+                    std::io::Error::other(format!("Invalid promotion piece: '{}'", input.piece)),
+                    &game,
+                ))
+            }
+        };
+        let (pending, mv) = session.take_pending_promotion().ok_or_else(|| {
+            ParseError::new(std::io::Error::other("No promotion is pending"), &game)
+        })?;
+        game = pending;
+        input.reason = "move".to_string();
+        input.moves = Move::with_promotion(mv.from, mv.to, piece).to_string();
+    }
 
-                if piece.to_ascii_lowercase() == 'p' || target != ' ' {
-                    game.halfmove = 0;
-                } else {
-                    game.halfmove += 1;
-                }
+    session.sync(&game);
 
-                // Handle pawn-specific moves
-                if piece.to_ascii_lowercase() == 'p' {
-                    // Double move - set en passant square
-                    if (moves.0 .0 as i8 - moves.1 .0 as i8).abs() == 2 {
-                        game.enpassat = Some(((moves.0 .0 + moves.1 .0) / 2, moves.0 .1));
-                    } else if moves.1 == enpassat {
-                        // En passant capture - remove the captured pawn
-                        game.board[moves.0 .0 as usize][moves.1 .1 as usize] = ' ';
-                    }
+    // Queries with their own small replies instead of the full game output
+    if input.reason == "count" {
+        let count = get_legal_moves(
+            &game.board,
+            game.enpassat.unwrap_or((0, 0)),
+            game.castling,
+            game.player,
+        )
+        .len();
+        return Ok(json!({ "legal_move_count": count }));
+    }
+    if input.reason == "mobility" {
+        let (white, black) = mobility(&game);
+        return Ok(json!({ "mobility": { "white": white, "black": black } }));
+    }
+    if input.reason == "matein1" {
+        let mates: Vec<String> = find_mate_in_one(&game).iter().map(Move::to_string).collect();
+        return Ok(json!({ "mate_in_one": mates }));
+    }
+    if input.reason == "quiet" {
+        return Ok(json!({ "quiet": is_quiet(&game) }));
+    }
+    if input.reason == "see" {
+        // The capture to evaluate comes in `moves`, e.g. "d4-e5"; centipawns for its side
+        let mv = parse_move(&input.moves)
+            .map_err(|e| ParseError::new(std::io::Error::other(e), &game))?;
+        return Ok(json!({ "see": see(&game, mv.from, mv.to) }));
+    }
+    if input.reason == "opening" {
+        return Ok(json!({ "opening": identify(&game) }));
+    }
+    if input.reason == "movemap" {
+        // Keyed by algebraic square, e.g. {"g1": ["f3", "h3"]}
+        let map: serde_json::Map<String, Value> = legal_moves_map(&game)
+            .into_iter()
+            .filter_map(|(from, destinations)| {
+                let to: Vec<String> = destinations
+                    .into_iter()
+                    .filter_map(index_to_chess_notation)
+                    .collect();
+                Some((index_to_chess_notation(from)?, json!(to)))
+            })
+            .collect();
+        return Ok(json!({ "movemap": map }));
+    }
+    if input.reason == "bestmove" {
+        // Score is in centipawns for the side to move
+        let (best, score) = match search_with(&game, session.search_depth(), session.evaluation()) {
+            Some((mv, score)) => (Some(mv.to_string()), Some(score)),
+            None => (None, None),
+        };
+        return Ok(json!({ "bestmove": best, "score": score }));
+    }
 
-                    // Pawn promotion - check if pawn reached opposite end
-                    if moves.1 .0 == 0 || moves.1 .0 == 7 {
-                        // Promote to Queen by default (can be extended to read from input.promotion)
-                        game.board[moves.1 .0 as usize][moves.1 .1 as usize] =
-                            if piece.is_uppercase() { 'Q' } else { 'q' };
-                    }
-                }
+    if input.reason == "start" {
+        message = "valid";
+    } else if input.reason == "new" {
+        // Ignore whatever FEN was sent and begin a fresh game
+        game = Gamestate::starting_position();
+        session.reset(&game);
+        legal_moves = side_to_move_moves(&game);
+        message = "valid";
+    } else if input.reason == "move" {
+        // No moves once the game has ended, so clients can tell this from an illegal move
+        let before = game_outcome(
+            &game,
+            &side_to_move_moves(&game),
+            session.repetition_count(&game),
+        );
+        if before.checkmate || before.stalemate {
+            return Err(ParseError::new(
+                std::io::Error::other(format!("Game is already over: {}", before.message)),
+                &game,
+            )
+            .with_status(before.status));
+        }
 
-                // FIX: Remove castling rights if rook is captured
-                if target == 'R' {
-                    match moves.1 {
-                        (7, 0) => game.castling.1 = '-', // White queenside rook captured
-                        (7, 7) => game.castling.0 = '-', // White kingside rook captured
-                        _ => (),
-                    }
-                } else if target == 'r' {
-                    match moves.1 {
-                        (0, 0) => game.castling.3 = '-', // Black queenside rook captured
-                        (0, 7) => game.castling.2 = '-', // Black kingside rook captured
-                        _ => (),
-                    }
-                }
+        let mut mv = parse_move(&input.moves)
+            .map_err(|e| ParseError::new(std::io::Error::other(e), &game))?;
+        // A piece in the notation itself wins over the `promotion` field
+        if mv.promotion.is_none()
+            && matches!(classify_move(&game, mv.from, mv.to, None), MoveKind::Promotion { .. })
+        {
+            mv.promotion = input.promotion;
+        }
+        let next = make_move(&game, mv.from, mv.to, mv.promotion).map_err(|e| {
+            // Say why when possible, e.g. that the piece belongs to the other side
+            let e = match explain_illegal(&game, mv.from, mv.to, mv.promotion) {
+                Some(reason) => format!("Illegal move {}: {}", mv, reason),
+                None => e,
+            };
+            ParseError::new(std::io::Error::other(e), &game)
+        })?;
+        if session.interactive_promotion()
+            && mv.promotion.is_none()
+            && matches!(classify_move(&game, mv.from, mv.to, None), MoveKind::Promotion { .. })
+        {
+            session.set_pending_promotion(&game, mv);
+            return Ok(json!({
+                "message": "promotion_required",
+                "options": PROMOTION_PIECES,
+            }));
+        }
+        game = next;
+        session.record(&game);
 
-                // Handle castling moves and update castling rights
-                if piece == 'K' && moves.0 == (7, 4) {
-                    game.castling.0 = '-';
-                    game.castling.1 = '-';
-                    match moves.1 {
-                        (7, 6) => {
-                            // Kingside castle: move rook
-                            game.board[7][5] = game.board[7][7];
-                            game.board[7][7] = ' ';
-                        }
-                        (7, 2) => {
-                            // Queenside castle: move rook
-                            game.board[7][3] = game.board[7][0];
-                            game.board[7][0] = ' ';
-                        }
-                        _ => {}
-                    }
-                } else if piece == 'k' && moves.0 == (0, 4) {
-                    game.castling.2 = '-';
-                    game.castling.3 = '-';
-                    match moves.1 {
-                        (0, 6) => {
-                            // Kingside castle: move rook
-                            game.board[0][5] = game.board[0][7];
-                            game.board[0][7] = ' ';
-                        }
-                        (0, 2) => {
-                            // Queenside castle: move rook
-                            game.board[0][3] = game.board[0][0];
-                            game.board[0][0] = ' ';
-                        }
-                        _ => {}
-                    }
-                } else if piece == 'R' {
-                    // White rook moved - update castling rights
-                    match moves.0 {
-                        (7, 0) => game.castling.1 = '-', // Queenside rook
-                        (7, 7) => game.castling.0 = '-', // Kingside rook
-                        _ => (),
-                    };
-                } else if piece == 'r' {
-                    // Black rook moved - update castling rights
-                    match moves.0 {
-                        (0, 0) => game.castling.3 = '-', // Queenside rook
-                        (0, 7) => game.castling.2 = '-', // Kingside rook
-                        _ => (),
-                    };
-                };
-
-                // Switch player
-                game.player = if game.player == 'w' {
-                    'b'
-                } else {
-                    game.fullmove += 1;
-                    'w'
-                };
-
-                // FIX: Check for 50-move rule draw
-                if game.halfmove >= 100 {
-                    message = "draw by 50-move rule";
-                    stalemate = true; // Use stalemate flag for draw
-                } else {
-                    // Check game status for the NEW player (who just got the turn)
-                    let status = get_game_status(
-                        &game.board,
-                        game.enpassat.unwrap_or((0, 0)),
-                        game.castling,
-                        game.player,
-                    );
-
-                    match status {
-                        GameStatus::Checkmate => {
-                            message = "checkmate";
-                            checkmate = true;
-                        }
-                        GameStatus::Stalemate => {
-                            message = "stalemate";
-                            stalemate = true;
-                        }
-                        GameStatus::Check => {
-                            message = "check";
-                            in_check = true;
-                        }
-                        GameStatus::Ongoing => {
-                            message = "valid";
-                        }
-                    }
-                }
-            } else {
-                ParseError::new(
-                    std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        "Illegal move made, skipping move",
-                    ),
-                    &game,
-                )
-                .print_stderr();
-                continue;
+        // Check game status for the NEW player (who just got the turn)
+        legal_moves = side_to_move_moves(&game);
+        outcome = game_outcome(&game, &legal_moves, session.repetition_count(&game));
+        message = outcome.message;
+        in_check = outcome.in_check;
+        checkmate = outcome.checkmate;
+        stalemate = outcome.stalemate;
+    } else if input.reason == "status" {
+        // Like a move reply for the position as sent, without playing anything
+        legal_moves = side_to_move_moves(&game);
+        outcome = game_outcome(&game, &legal_moves, session.repetition_count(&game));
+        message = outcome.message;
+        in_check = outcome.in_check;
+        checkmate = outcome.checkmate;
+        stalemate = outcome.stalemate;
+    } else if input.reason == "validate" {
+        match game.is_legal_position() {
+            Ok(_) => {
+                legal_moves = side_to_move_moves(&game);
+                in_check = is_in_check(
+                    &game.board,
+                    game.enpassat.unwrap_or((0, 0)),
+                    game.castling,
+                    game.player,
+                );
+                message = "valid";
             }
-        } else if input.reason == "validate" {
-            match validate_board(&game.board) {
-                Ok(_) => {
-                    message = "valid";
-                }
-                Err(e) => {
-                    ParseError::new(
-                        std::io::Error::new(std::io::ErrorKind::Other, e),
-                        &game,
-                    )
-                    .print_stderr();
-                    continue;
-                }
+            Err(e) => {
+                return Err(ParseError::new(std::io::Error::other(e), &game));
             }
         }
+    }
 
-        let legal_moves: Vec<((u8, u8), (u8, u8))> = if input.reason == "validate"
-            || (input.reason == "move" && message != "checkmate" && message != "stalemate" && message != "draw by 50-move rule")
-        {
-            get_legal_moves(
-                &game.board,
-                game.enpassat.unwrap_or((0, 0)),
-                game.castling,
-                game.player,
-            )
-        } else {
-            Vec::new()
-        };
+    // A finished game offers no moves, even when a rule draw leaves some on the board
+    if checkmate || stalemate {
+        legal_moves.clear();
+    }
+
+    let legal_moves: Vec<String> = legal_moves
+        .into_iter()
+        .filter_map(|(x, y): ((u8, u8), (u8, u8))| {
+            match (index_to_chess_notation(x), index_to_chess_notation(y)) {
+                (Some(from), Some(to)) => Some(format!("{}-{}", from, to)),
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Reasons without a full outcome still report a check found above
+    let status = match outcome.status {
+        GameStatus::Ongoing if in_check => GameStatus::Check,
+        status => status,
+    };
+
+    let repetition_count = session.repetition_count(&game);
+    Ok(ParseOut::new(
+        message.to_string(),
+        &game,
+        legal_moves,
+        in_check,
+        checkmate,
+        stalemate,
+    )
+    .with_status(status)
+    .with_repetition_count(repetition_count)
+    .with_practical_draw(material_verdict(&game.board) == MaterialVerdict::PracticalDraw)
+    .with_result(outcome.draw_reason, outcome.winner)
+    .with_material(session.reports_material().then(|| Material::of(&game.board)))
+    .with_board(
+        input
+            .include_board
+            .then(|| orient_board(&game.board_string(), input.orientation)),
+    )
+    .to_json())
+}
 
-        // Check if current player is in check (for status reporting)
-        if !checkmate && !stalemate && input.reason != "start" {
-            in_check = is_in_check(
+/// Checks a candidate move without playing it, for `trymove`: the SAN and resulting FEN
+/// if it is legal, otherwise why not
+fn try_move(game: &Gamestate, notation: &str) -> Value {
+    let mv = match parse_move(notation) {
+        Ok(mv) => mv,
+        Err(e) => return json!({ "legal": false, "reason": e }),
+    };
+    match make_move(game, mv.from, mv.to, mv.promotion) {
+        Ok(next) => json!({
+            "legal": true,
+            "san": move_to_san(game, &mv),
+            "resulting_fen": next.to_fen(),
+        }),
+        Err(e) => {
+            let reason = explain_illegal(game, mv.from, mv.to, mv.promotion).unwrap_or(e);
+            json!({ "legal": false, "reason": reason })
+        }
+    }
+}
+
+/// How the game stands in a position, as reported after a move or for `status`
+#[derive(Default)]
+struct Outcome {
+    message: &'static str,
+    status: GameStatus,
+    in_check: bool,
+    checkmate: bool,
+    /// Set for every draw, not only stalemate
+    stalemate: bool,
+    draw_reason: Option<&'static str>,
+    /// `"white"` or `"black"` after checkmate
+    winner: Option<&'static str>,
+}
+
+/// The legal moves for the side to move in `game`
+fn side_to_move_moves(game: &Gamestate) -> Vec<CoordMove> {
+    get_legal_moves(
+        &game.board,
+        game.enpassat.unwrap_or((0, 0)),
+        game.castling,
+        game.player,
+    )
+}
+
+/// Works out the outcome for the side to move in `game`, whose legal moves are
+/// `legal_moves` and whose position has occurred `repetition_count` times: the 50-move
+/// rule, dead positions, fivefold repetition, then checkmate, stalemate and check.
+/// Threefold repetition doesn't end the game, the reply only marks the draw claimable.
+fn game_outcome(game: &Gamestate, legal_moves: &[CoordMove], repetition_count: u8) -> Outcome {
+    let draw = |message, status, reason| Outcome {
+        message,
+        status,
+        stalemate: true, // Use stalemate flag for draw
+        draw_reason: Some(reason),
+        ..Outcome::default()
+    };
+
+    // FIX: Check for 50-move rule draw
+    if game.halfmove >= 100 {
+        return draw("draw by 50-move rule", GameStatus::DrawByFiftyMoveRule, "50-move rule");
+    }
+    if material_verdict(&game.board) == MaterialVerdict::DeadPosition {
+        return draw(
+            "draw by insufficient material",
+            GameStatus::DrawByInsufficientMaterial,
+            "insufficient material",
+        );
+    }
+    if is_fivefold_count(repetition_count) && !legal_moves.is_empty() {
+        return draw(
+            "draw by fivefold repetition",
+            GameStatus::DrawByRepetition,
+            "fivefold repetition",
+        );
+    }
+
+    let in_check = is_in_check(
+        &game.board,
+        game.enpassat.unwrap_or((0, 0)),
+        game.castling,
+        game.player,
+    );
+    let status = match (legal_moves.is_empty(), in_check) {
+        (true, true) => GameStatus::Checkmate,
+        (true, false) => GameStatus::Stalemate,
+        (false, true) => GameStatus::Check,
+        (false, false) => GameStatus::Ongoing,
+    };
+    match status {
+        GameStatus::Checkmate => Outcome {
+            message: "checkmate",
+            status,
+            checkmate: true,
+            winner: Some(if game.player == 'w' { "black" } else { "white" }),
+            ..Outcome::default()
+        },
+        GameStatus::Stalemate => draw("stalemate", status, "stalemate"),
+        GameStatus::Check => Outcome {
+            message: "check",
+            status,
+            in_check: true,
+            ..Outcome::default()
+        },
+        _ => Outcome {
+            message: "valid",
+            ..Outcome::default()
+        },
+    }
+}
+
+/// Times move generation and perft over the bench suite and prints nodes/second
+fn bench(depth: u32) {
+    const GENERATION_RUNS: u32 = 1000;
+
+    let mut total_nodes: u64 = 0;
+    let mut total_seconds: f64 = 0.0;
+    for (name, fen) in BENCH_POSITIONS {
+        let game = Gamestate::from_str(fen).expect("Bench positions are valid FEN");
+
+        let started = Instant::now();
+        for _ in 0..GENERATION_RUNS {
+            get_legal_moves(
                 &game.board,
                 game.enpassat.unwrap_or((0, 0)),
                 game.castling,
                 game.player,
             );
         }
+        let generation = started.elapsed().as_secs_f64() / GENERATION_RUNS as f64;
 
-        let legal_moves: Vec<String> = legal_moves
-            .into_iter()
-            .filter_map(|(x, y): ((u8, u8), (u8, u8))| {
-                match (index_to_chess_notation(x), index_to_chess_notation(y)) {
-                    (Some(from), Some(to)) => Some(format!("{}-{}", from, to)),
-                    _ => None,
-                }
-            })
-            .collect::<Vec<_>>();
+        let started = Instant::now();
+        let nodes = perft(&game, depth);
+        let seconds = started.elapsed().as_secs_f64();
+        total_nodes += nodes;
+        total_seconds += seconds;
 
-        ParseOut::new(
-            message.to_string(),
-            &game,
-            legal_moves,
-            in_check,
-            checkmate,
-            stalemate,
-        )
-        .print_stdout();
+        println!(
+            "{:<14} legal moves: {:>8.1} us \t perft({}): {:>10} nodes in {:>7.3} s ({:.0} nodes/s)",
+            name,
+            generation * 1e6,
+            depth,
+            nodes,
+            seconds,
+            nodes as f64 / seconds
+        );
     }
+    println!(
+        "{:<14} {} nodes in {:.3} s ({:.0} nodes/s)",
+        "total",
+        total_nodes,
+        total_seconds,
+        total_nodes as f64 / total_seconds
+    );
 }
 
-fn process_pgn(pgn_input: &str) -> Result<(), Box<dyn std::error::Error>> {
-    // Try to parse as PGN
-    let game = PgnGame::from_pgn(pgn_input)?;
-    
-    println!("PGN Game Parsed Successfully!");
-    println!("==========================================");
-    
-    // Display headers
-    if let Some(event) = game.get_header("Event") {
-        println!("Event: {}", event);
-    }
-    if let Some(site) = game.get_header("Site") {
-        println!("Site: {}", site);
+/// Runs each line of a perft-EPD suite and prints PASS or FAIL per position, with the
+/// depths that went wrong
+fn perft_epd(suite: &str) {
+    let (mut passed, mut total) = (0, 0);
+    for line in suite.lines().filter(|line| !line.trim().is_empty()) {
+        total += 1;
+        match perft_epd_line(line) {
+            Ok(result) if result.passed() => {
+                passed += 1;
+                println!("PASS {}", result.fen);
+            }
+            Ok(result) => {
+                println!("FAIL {}", result.fen);
+                for (depth, expected, actual) in result.depths {
+                    if expected != actual {
+                        println!("\t D{}: expected {}, got {}", depth, expected, actual);
+                    }
+                }
+            }
+            Err(e) => println!("FAIL {}: {}", line.trim(), e),
+        }
     }
-    if let Some(date) = game.get_header("Date") {
-        println!("Date: {}", date);
+    println!("{}/{} positions passed", passed, total);
+}
+
+/// Parses a PGN game for `--pgn` and `--pgn-file` and reports it as `config` asks:
+/// printed unless `--no-print` is set, and saved to the `--output` file if one is given
+fn process_pgn(pgn_input: &str, config: &Config) -> Result<(), String> {
+    let summary = pgn_parser::process_pgn(pgn_input)?;
+    let report = pgn_summary_text(&summary);
+    if !config.no_print {
+        print!("{}", report);
     }
-    if let Some(white) = game.get_header("White") {
-        println!("White: {}", white);
+    if !config.output.is_empty() {
+        std::fs::write(&config.output, &report)
+            .map_err(|e| format!("cannot write '{}': {}", config.output, e))?;
     }
-    if let Some(black) = game.get_header("Black") {
-        println!("Black: {}", black);
+    Ok(())
+}
+
+/// Formats a parsed PGN game: its headers, the numbered moves and the game as PGN
+fn pgn_summary_text(summary: &PgnSummary) -> String {
+    // Writing to a String can't fail
+    let mut text = String::new();
+    let _ = writeln!(text, "PGN Game Parsed Successfully!");
+    let _ = writeln!(text, "==========================================");
+
+    // Display headers
+    for (tag, value) in &summary.headers {
+        let _ = writeln!(text, "{}: {}", tag, value);
     }
-    println!("Result: {}", game.result);
-    println!("==========================================");
-    
+    let _ = writeln!(text, "Result: {}", summary.result);
+    let _ = writeln!(text, "==========================================");
+
     // Display moves
-    println!("\nMoves ({} total):", game.moves.len());
-    for (i, san_move) in game.moves.iter().enumerate() {
+    let _ = writeln!(text, "\nMoves ({} total):", summary.moves.len());
+    for (i, san_move) in summary.moves.iter().enumerate() {
         let move_num = (i / 2) + 1;
         if i % 2 == 0 {
-            print!("{}. {} ", move_num, san_move);
+            let _ = write!(text, "{}. {} ", move_num, san_move);
         } else {
-            println!("{}", san_move);
+            let _ = writeln!(text, "{}", san_move);
         }
     }
-    if game.moves.len() % 2 == 1 {
-        println!(); // New line if odd number of moves
+    if summary.moves.len() % 2 == 1 {
+        text.push('\n'); // New line if odd number of moves
     }
-    
-    println!("\n==========================================");
-    println!("PGN Output:");
-    println!("{}", game.to_pgn());
-    
-    Ok(())
+
+    let _ = writeln!(text, "\n==========================================");
+    let _ = writeln!(text, "PGN Output:");
+    let _ = writeln!(text, "{}", summary.pgn_text);
+    text
 }
 
-fn main() {
-    let mut args: Vec<String> = env::args().collect();
-    args.remove(0);
-    if args.is_empty() {
-        args.push("--help".to_string());
-    }
-    let mut file: String = String::new();
-    let mut pgn_file: String = String::new();
-    let mut pgn_string: String = String::new();
-    let mut skip: bool = false;
-    let (mut cli_mode, mut test, mut no_print, mut verbose): (bool, bool, bool, u8) =
-        (false, false, false, 0);
-    for v in args.iter() {
-        if skip {
-            skip = false;
-            continue;
+/// Command-line options, parsed once from the arguments
+#[derive(Debug, Clone, PartialEq)]
+struct Config {
+    help: bool,
+    cli_mode: bool,
+    /// Leave the PGN report off the terminal, e.g. when it goes to `output`
+    no_print: bool,
+    /// File to save the PGN report to
+    output: String,
+    pgn_file: String,
+    pgn_string: String,
+    bench: bool,
+    /// Perft-EPD suite to check the move generator against
+    perft_epd: String,
+    evaluation: Evaluation,
+    depth: u32,
+    material: bool,
+    interactive_promotion: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            help: false,
+            cli_mode: false,
+            no_print: false,
+            output: String::new(),
+            pgn_file: String::new(),
+            pgn_string: String::new(),
+            bench: false,
+            perft_epd: String::new(),
+            evaluation: Evaluation::Material,
+            depth: DEFAULT_SEARCH_DEPTH,
+            material: false,
+            interactive_promotion: false,
         }
-        match v.as_str() {
-            "--help" | "-h" => {
-                println!("Usage: chess [options]");
-                println!("commands:");
-                println!("\t -h \t --help \t\t: Prints this help message: Default = true");
-                println!(
-                    "\t -c \t --cli \t\t: To enable the JSP mode for cli usage; Default = false"
-                );
-                println!("\t -o \t --output <file>\t: Saves the output to a file; Default = None");
-                println!("\t -n \t --no-print \t\t: Does not print to command line. For use with --output; Default = false");
-                println!("\t -t \t --test \t\t: Runs the test suite: Default = False");
-                println!("\t -v \t --verbose <0-4>\t: Uses more verbose messaging (0-4): Default = 0");
-                println!("\t \t \t\t  4 - Debug \t 3 - Info \t 2 - Warning \t 1 - Error \t 0 - Fatal");
-                println!("\t -p \t --pgn <string>\t: Parse PGN string directly");
-                println!("\t -f \t --pgn-file <file>\t: Read and parse PGN from file");
-                println!("\nExamples:");
-                println!("  chess --cli");
-                println!("  chess --pgn-file game.pgn");
-                println!("  chess --pgn '[Event \"Test\"] 1. e4 e5 2. Nf3'");
-            }
-            "--test" | "-t" => test = true,
-            "--output" | "-o" => {
-                skip = true;
-                file = args[args.iter().position(|x: &String| x == v).unwrap() + 1].clone()
-            }
-            "--verbose" | "-v" => {
-                skip = true;
-                verbose = args[args.iter().position(|x: &String| x == v).unwrap() + 1]
-                    .parse()
-                    .unwrap()
-            }
-            "--pgn" | "-p" => {
-                skip = true;
-                pgn_string = args[args.iter().position(|x: &String| x == v).unwrap() + 1].clone()
-            }
-            "--pgn-file" | "-f" => {
-                skip = true;
-                pgn_file = args[args.iter().position(|x: &String| x == v).unwrap() + 1].clone()
+    }
+}
+
+impl Config {
+    /// Parses the arguments after the program name. No arguments at all asks for help;
+    /// unknown flags are ignored, and a flag missing its value keeps the default.
+    fn from_args(args: &[String]) -> Self {
+        let mut config = Config {
+            help: args.is_empty(),
+            ..Config::default()
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--help" | "-h" => config.help = true,
+                "--output" | "-o" => config.output = args.next().cloned().unwrap_or_default(),
+                "--pgn" | "-p" => config.pgn_string = args.next().cloned().unwrap_or_default(),
+                "--pgn-file" | "-f" => config.pgn_file = args.next().cloned().unwrap_or_default(),
+                "--cli" | "-c" => config.cli_mode = true,
+                "--no-print" | "-n" => config.no_print = true,
+                "--bench" | "-b" => config.bench = true,
+                "--perft-epd" => config.perft_epd = args.next().cloned().unwrap_or_default(),
+                "--positional" => config.evaluation = Evaluation::Positional,
+                "--material" => config.material = true,
+                "--interactive-promotion" => config.interactive_promotion = true,
+                "--depth" | "-d" => {
+                    config.depth = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(config.depth)
+                }
+                _ => (),
             }
-            "--cli" | "-c" => cli_mode = true,
-            "--no-print" | "-n" => no_print = true,
-            _ => (),
         }
+        config
     }
-    
+}
+
+fn print_help() {
+    println!("Usage: chess [options]");
+    println!("commands:");
+    println!("\t -h \t --help \t\t: Prints this help message: Default = true");
+    println!("\t -c \t --cli \t\t: To enable the JSP mode for cli usage; Default = false");
+    println!("\t -o \t --output <file>\t: Saves the PGN report to a file; Default = None");
+    println!("\t -n \t --no-print \t\t: Does not print the PGN report. For use with --output; Default = false");
+    println!("\t -p \t --pgn <string>\t: Parse PGN string directly");
+    println!("\t -f \t --pgn-file <file>\t: Read and parse PGN from file");
+    println!("\t -b \t --bench \t\t: Times move generation and perft(4) over a fixed position suite");
+    println!("\t \t --perft-epd <file>\t: Checks perft counts against a suite of '<FEN> ;D1 20 ;D2 400' lines");
+    println!("\t -d \t --depth <plies>\t: Search depth for bestmove requests; Default = 2");
+    println!("\t \t --positional \t\t: Scores positions with piece-square tables on top of material; Default = false");
+    println!("\t \t --material \t\t: Adds each side's material to move replies; Default = false");
+    println!("\t \t --interactive-promotion : Asks for the piece when a promotion is sent without one; Default = false");
+    println!("\nExamples:");
+    println!("  chess --cli");
+    println!("  chess --pgn-file game.pgn");
+    println!("  chess --pgn '[Event \"Test\"] 1. e4 e5 2. Nf3'");
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let config = Config::from_args(&args);
+    if config.help {
+        print_help();
+    }
+
     // Process PGN if requested
-    if !pgn_string.is_empty() {
-        match process_pgn(&pgn_string) {
-            Ok(_) => {},
-            Err(e) => eprintln!("Error parsing PGN string: {}", e),
+    if !config.pgn_string.is_empty() {
+        if let Err(e) = process_pgn(&config.pgn_string, &config) {
+            eprintln!("Error processing PGN string: {}", e);
         }
         return;
     }
     
-    if !pgn_file.is_empty() {
-        match std::fs::read_to_string(&pgn_file) {
+    if !config.pgn_file.is_empty() {
+        match std::fs::read_to_string(&config.pgn_file) {
             Ok(content) => {
-                match process_pgn(&content) {
-                    Ok(_) => {},
-                    Err(e) => eprintln!("Error parsing PGN file: {}", e),
+                if let Err(e) = process_pgn(&content, &config) {
+                    eprintln!("Error processing PGN file: {}", e);
                 }
             },
-            Err(e) => eprintln!("Error reading file '{}': {}", pgn_file, e),
+            Err(e) => eprintln!("Error reading file '{}': {}", config.pgn_file, e),
         }
         return;
     }
     
-    if test {}
-    if file != "" {
-        if no_print {}
+    if config.bench {
+        bench(4);
+        return;
+    }
+
+    if !config.perft_epd.is_empty() {
+        match std::fs::read_to_string(&config.perft_epd) {
+            Ok(content) => perft_epd(&content),
+            Err(e) => eprintln!("Error reading file '{}': {}", config.perft_epd, e),
+        }
+        return;
+    }
+
+    if config.cli_mode {
+        cli(&config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::parsers::parse_input::parse_input;
+    use serde_json::Value;
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    /// Sends one request through the handler and returns the JSON reply
+    fn request(session: &mut Session, line: &str) -> Value {
+        let input = parse_input(line).unwrap();
+        match handle_input(session, input) {
+            Ok(output) => output,
+            Err(e) => e.to_json(),
+        }
+    }
+
+    /// Sends a request that only needs a position and returns the reply
+    fn query(session: &mut Session, reason: &str, fen: &str) -> Value {
+        request(session, &format!(r#"{{"reason":"{}","fen":"{}"}}"#, reason, fen))
+    }
+
+    /// Plays `mv` on `fen` and returns the reply
+    fn play(session: &mut Session, fen: &str, mv: &str) -> Value {
+        request(
+            session,
+            &format!(r#"{{"reason":"move","fen":"{}","moves":"{}"}}"#, fen, mv),
+        )
+    }
+
+    #[test]
+    fn test_repetition_count() {
+        let mut session = Session::new();
+        let mut fen = START_FEN.to_string();
+        let shuffle = ["g1-f3", "g8-f6", "f3-g1", "f6-g8"];
+
+        let mut counts = Vec::new();
+        for _ in 0..2 {
+            for mv in shuffle {
+                let reply = play(&mut session, &fen, mv);
+                fen = reply["fen"].as_str().unwrap().to_string();
+                counts.push(reply["repetition_count"].as_u64().unwrap());
+            }
+        }
+
+        // Back at the start position after each full knight shuffle
+        assert_eq!(counts, vec![1, 1, 1, 2, 2, 2, 2, 3]);
+        let reply = query(&mut session, "start", &fen);
+        assert_eq!(reply["draw_claimable"], true);
+
+        // Nobody claimed the draw, so the game goes on
+        let reply = play(&mut session, &fen, "g1-f3");
+        assert_eq!(reply["game_over"], false);
+        assert_eq!(reply["repetition_count"], 3);
+    }
+
+    #[test]
+    fn test_fivefold_repetition_draw() {
+        let mut session = Session::new();
+        let mut fen = START_FEN.to_string();
+        let mut replies = Vec::new();
+        for mv in ["g1-f3", "g8-f6", "f3-g1", "f6-g8"].iter().cycle().take(16) {
+            let reply = play(&mut session, &fen, mv);
+            fen = reply["fen"].as_str().unwrap().to_string();
+            replies.push(reply);
+        }
+
+        // The third occurrence of the start position only makes the draw claimable
+        let third = &replies[7];
+        assert_eq!(third["draw_claimable"], true);
+        assert_eq!(third["game_over"], false);
+        assert_eq!(third["status"], "ongoing");
+        assert_eq!(third["possible_moves"].as_array().unwrap().len(), 20);
+
+        // The fifth ends the game
+        let game_over: Vec<bool> =
+            replies.iter().map(|reply| reply["game_over"].as_bool().unwrap()).collect();
+        assert_eq!(game_over.iter().position(|&over| over), Some(15));
+        let reply = &replies[15];
+        assert_eq!(reply["message"], "draw by fivefold repetition");
+        assert_eq!(reply["status"], "draw_by_repetition");
+        assert_eq!(reply["game_over"], true);
+        assert_eq!(reply["possible_moves"].as_array().unwrap().len(), 0);
+
+        let reply = play(&mut session, &fen, "g1-f3");
+        assert!(reply["error"].as_str().unwrap().contains("Game is already over"));
+    }
+
+    #[test]
+    fn test_insufficient_material_draw() {
+        let mut session = Session::new();
+        // Taking the last pawn leaves king and knight against king
+        let reply = play(&mut session, "4k3/8/8/8/8/8/4p3/3NK3 w - - 0 1", "e1-e2");
+        assert_eq!(reply["message"], "draw by insufficient material");
+        assert_eq!(reply["game_over"], true);
+        assert_eq!(reply["possible_moves"].as_array().unwrap().len(), 0);
+
+        // Knight against knight is only a practical draw, so play goes on
+        let reply = play(&mut session, "4kn2/8/8/8/8/8/4p3/3NK3 w - - 0 1", "e1-e2");
+        assert_eq!(reply["message"], "valid");
+        assert_eq!(reply["practical_draw"], true);
+    }
+
+    #[test]
+    fn test_status_reason() {
+        let mut session = Session::new();
+        let status = |session: &mut Session, fen: &str| query(session, "status", fen);
+
+        // Fool's mate, black has just delivered it
+        let mated = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3";
+        let reply = status(&mut session, mated);
+        assert_eq!(reply["message"], "checkmate");
+        assert_eq!(reply["checkmate"], true);
+        assert_eq!(reply["winner"], "black");
+        assert_eq!(reply["draw_reason"], Value::Null);
+        assert_eq!(reply["possible_moves"].as_array().unwrap().len(), 0);
+
+        let reply = status(&mut session, "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(reply["stalemate"], true);
+        assert_eq!(reply["draw_reason"], "stalemate");
+
+        let reply = status(&mut session, START_FEN);
+        assert_eq!(reply["message"], "valid");
+        assert_eq!(reply["possible_moves"].as_array().unwrap().len(), 20);
+    }
+
+    #[test]
+    fn test_structured_status() {
+        let mut session = Session::new();
+        let reply = query(&mut session, "status", "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(reply["status"], "stalemate");
+        assert_eq!(reply["message"], "stalemate");
+
+        let reply = play(&mut session, "4k3/8/8/8/8/8/4p3/3NK3 w - - 0 1", "e1-e2");
+        assert_eq!(reply["status"], "draw_by_insufficient_material");
+
+        let reply = query(&mut session, "status", "4k3/8/8/8/8/8/8/R3K3 w - - 100 80");
+        assert_eq!(reply["status"], "draw_by_fifty_move_rule");
+
+        let reply = query(&mut session, "validate", "4k3/4R3/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(reply["status"], "check");
+        assert_eq!(query(&mut session, "status", START_FEN)["status"], "ongoing");
+    }
+
+    #[test]
+    fn test_promotion_move() {
+        let mut session = Session::new();
+        let reply = play(&mut session, "4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7-a8n");
+        assert_eq!(reply["fen"], "N3k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        let reply = play(&mut session, "4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7-a8=R");
+        assert_eq!(reply["fen"], "R3k3/8/8/8/8/8/8/4K3 b - - 0 1");
+
+        // Without a letter the pawn becomes a queen
+        let reply = play(&mut session, "4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7-a8");
+        assert_eq!(reply["fen"], "Q3k3/8/8/8/8/8/8/4K3 b - - 0 1");
+
+        // The piece may also come in the `promotion` field
+        let line = json!({
+            "reason": "move",
+            "fen": "4k3/P7/8/8/8/8/8/4K3 w - - 0 1",
+            "moves": "a7-a8",
+            "promotion": "N"
+        });
+        let reply = request(&mut session, &line.to_string());
+        assert_eq!(reply["fen"], "N3k3/8/8/8/8/8/8/4K3 b - - 0 1");
+
+        let reply = play(&mut session, "4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7-a8x");
+        assert!(reply.to_string().contains("Invalid promotion piece"));
+        let reply = play(&mut session, "4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "e1-e2q");
+        assert_ne!(reply["message"], "valid");
+        let reply = play(&mut session, "4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "e1-e2=Q");
+        assert!(reply.to_string().contains("is not a promotion"));
+    }
+
+    #[test]
+    fn test_trymove_reason() {
+        let mut session = Session::new();
+        let try_move = |session: &mut Session, mv: &str| {
+            request(
+                session,
+                &format!(r#"{{"reason":"trymove","fen":"{}","moves":"{}"}}"#, START_FEN, mv),
+            )
+        };
+
+        let reply = try_move(&mut session, "g1-f3");
+        assert_eq!(
+            reply,
+            json!({
+                "legal": true,
+                "san": "Nf3",
+                "resulting_fen": "rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 1 1"
+            })
+        );
+
+        let reply = try_move(&mut session, "g1-g3");
+        assert_eq!(reply["legal"], false);
+        assert_eq!(reply["reason"], "the knight on g1 cannot move to g3");
+        let reply = try_move(&mut session, "e7-e5");
+        assert_eq!(reply["reason"], "not your turn: the piece on e7 is black and white is to move");
+
+        // Nothing was recorded: the start position is still seen once
+        assert_eq!(query(&mut session, "status", START_FEN)["repetition_count"], 1);
+    }
+
+    #[test]
+    fn test_move_after_game_over() {
+        let mut session = Session::new();
+        // Fool's mate: White is checkmated
+        let mated = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3";
+        let reply = play(&mut session, mated, "a2-a3");
+        assert_eq!(reply["status"], "checkmate");
+        assert_eq!(reply["error"], "Game is already over: checkmate");
+
+        let reply = play(&mut session, "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", "h8-h7");
+        assert_eq!(reply["status"], "stalemate");
+
+        // An ordinary illegal move carries no status
+        let reply = play(&mut session, START_FEN, "e2-e5");
+        assert!(reply.get("error").is_some());
+        assert!(reply.get("status").is_none());
+    }
+
+    #[test]
+    fn test_movemap_reason() {
+        let mut session = Session::new();
+        let reply = query(&mut session, "movemap", START_FEN);
+        let map = reply["movemap"].as_object().unwrap();
+        let destinations = |square: &str| -> Vec<&str> {
+            let mut squares: Vec<&str> = map[square]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|to| to.as_str().unwrap())
+                .collect();
+            squares.sort();
+            squares
+        };
+
+        // Every pawn and both knights, with two destinations each
+        let mut sources: Vec<&str> = map.keys().map(String::as_str).collect();
+        sources.sort();
+        assert_eq!(sources, ["a2", "b1", "b2", "c2", "d2", "e2", "f2", "g1", "g2", "h2"]);
+        for square in sources {
+            assert_eq!(destinations(square).len(), 2, "{}", square);
+        }
+        assert_eq!(destinations("e2"), ["e3", "e4"]);
+        assert_eq!(destinations("g1"), ["f3", "h3"]);
+    }
+
+    #[test]
+    fn test_fullmove_after_black_move_from_fen() {
+        let mut session = Session::new();
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 5";
+        let reply = play(&mut session, fen, "g8-f6");
+        assert_eq!(
+            reply["fen"],
+            "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 1 6"
+        );
+
+        // White's reply keeps the move number
+        let reply = play(&mut session, reply["fen"].as_str().unwrap(), "b1-c3");
+        assert!(reply["fen"].as_str().unwrap().ends_with("b KQkq - 2 6"));
+    }
+
+    #[test]
+    fn test_interactive_promotion() {
+        let fen = "k7/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        let mut session = Session::new().with_interactive_promotion(true);
+        let reply = play(&mut session, fen, "e7-e8");
+        let options = json!(["q", "r", "b", "n"]);
+        assert_eq!(reply, json!({ "message": "promotion_required", "options": options }));
+
+        let reply = request(&mut session, r#"{"reason":"promote","piece":"n"}"#);
+        assert_eq!(reply["fen"], "k3N3/8/8/8/8/8/8/4K3 b - - 0 1");
+
+        // Nothing is left waiting, and the default still queens straight away
+        let reply = request(&mut session, r#"{"reason":"promote","piece":"n"}"#);
+        assert_eq!(reply["error"], "No promotion is pending");
+        let reply = play(&mut Session::new(), fen, "e7-e8");
+        assert_eq!(reply["fen"], "k3Q3/8/8/8/8/8/8/4K3 b - - 0 1");
+    }
+
+    #[test]
+    fn test_uci_move() {
+        let fen = "k7/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        let reply = play(&mut Session::new(), fen, "e7e8r");
+        assert_eq!(reply["fen"], "k3R3/8/8/8/8/8/8/4K3 b - - 0 1");
+        let reply = play(&mut Session::new(), START_FEN, "g1f3");
+        assert_eq!(reply["message"], "valid");
+    }
+
+    #[test]
+    fn test_config_from_args() {
+        let args: Vec<String> = ["--cli", "-d", "4", "--positional", "--pgn-file", "game.pgn"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let expected = Config {
+            cli_mode: true,
+            depth: 4,
+            evaluation: Evaluation::Positional,
+            pgn_file: "game.pgn".to_string(),
+            ..Config::default()
+        };
+        assert_eq!(Config::from_args(&args), expected);
+        assert!(Config::from_args(&[]).help);
+
+        // A flag missing its value keeps the default
+        assert_eq!(Config::from_args(&["--depth".to_string()]).depth, DEFAULT_SEARCH_DEPTH);
+    }
+
+    #[test]
+    fn test_process_pgn_output_file() {
+        let output = env::temp_dir().join(format!("pgn_report_{}.txt", std::process::id()));
+        let config = Config {
+            no_print: true,
+            output: output.to_string_lossy().into_owned(),
+            ..Config::default()
+        };
+
+        process_pgn("[White \"A\"]\n\n1. e4 e5 2. Nf3 *", &config).unwrap();
+        let report = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_file(&output).unwrap();
+        assert!(report.contains("White: A\n"));
+        assert!(report.contains("Moves (3 total):\n1. e4 e5\n2. Nf3 \n"));
+
+        assert!(process_pgn("1. e4 e5 2. Qxz9", &config).is_err());
+    }
+
+    #[test]
+    fn test_move_out_of_turn() {
+        let reply = play(&mut Session::new(), START_FEN, "e7-e5");
+        assert_eq!(
+            reply["error"],
+            "Illegal move e7-e5: not your turn: the piece on e7 is black and white is to move"
+        );
     }
-    if verbose > 0 {}
-    if cli_mode {
-        cli()
+
+    #[test]
+    fn test_include_board() {
+        let mut session = Session::new();
+        let line = format!(r#"{{"reason":"status","fen":"{}","include_board":true}}"#, START_FEN);
+        let board = request(&mut session, &line)["board"].as_str().unwrap().to_string();
+
+        let mut game = Gamestate::new();
+        game.set_board_string(&board).unwrap();
+        assert_eq!(game.board, Gamestate::starting_position().board);
+        assert!(query(&mut session, "status", START_FEN).get("board").is_none());
+    }
+
+    #[test]
+    fn test_move_reply_moves_and_check() {
+        // After Bb5+ Black must block the check; the king cannot step onto d7
+        let fen = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2";
+        let reply = play(&mut Session::new(), fen, "f1-b5");
+        assert_eq!(reply["in_check"], true);
+        assert_eq!(reply["status"], "check");
+        let mut moves: Vec<&str> = reply["possible_moves"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|mv| mv.as_str().unwrap())
+            .collect();
+        moves.sort();
+        assert_eq!(moves, ["b8-c6", "b8-d7", "c7-c6", "c8-d7", "d8-d7"]);
+
+        let reply = query(&mut Session::new(), "validate", START_FEN);
+        assert_eq!(reply["possible_moves"].as_array().unwrap().len(), 20);
+        assert_eq!(reply["in_check"], false);
+    }
+
+    #[test]
+    fn test_en_passant_field_matches_fen() {
+        // After 1. e4 no black pawn can take on e3
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        let reply = query(&mut Session::new(), "validate", fen);
+        assert_eq!(reply["en_passant"], Value::Null);
+        assert!(reply["fen"].as_str().unwrap().ends_with("b KQkq - 0 1"));
+    }
+
+    #[test]
+    fn test_black_orientation() {
+        // Only a1 and h8 are occupied, besides the kings
+        let fen = "4k2r/8/8/8/8/8/8/R3K3 w - - 0 1";
+        let reply = |orientation: &str| {
+            let line = format!(
+                r#"{{"reason":"status","fen":"{}","orientation":"{}"}}"#,
+                fen, orientation
+            );
+            request(&mut Session::new(), &line)
+        };
+        let board = |reply: &Value| reply["board"].as_str().unwrap().chars().collect::<Vec<_>>();
+
+        let white = reply("white");
+        assert_eq!((board(&white)[56], board(&white)[7]), ('R', 'r'));
+        // From Black's side a1 is drawn in the top-right corner and h8 bottom-left
+        let black = reply("black");
+        assert_eq!((board(&black)[7], board(&black)[56]), ('R', 'r'));
+        // The moves keep their algebraic squares either way
+        assert_eq!(black["possible_moves"], white["possible_moves"]);
+
+        let line = format!(r#"{{"reason":"status","fen":"{}","orientation":"red"}}"#, fen);
+        assert!(parse_input(&line).is_err());
+    }
+
+    #[test]
+    fn test_material_report() {
+        let fen = "4k3/pppp4/8/8/8/8/PPPP4/4KN2 w - - 0 1";
+        let reply = play(&mut Session::new(), fen, "a2-a3");
+        assert!(reply.get("material").is_none());
+
+        let mut session = Session::new().with_material_report(true);
+        let reply = play(&mut session, fen, "a2-a3");
+        assert_eq!(reply["material"], json!({ "white": 720, "black": 400, "diff": 320 }));
+    }
+
+    #[test]
+    fn test_quiet_reason() {
+        let mut session = Session::new();
+        assert_eq!(query(&mut session, "quiet", START_FEN), json!({ "quiet": true }));
+        let reply = query(&mut session, "quiet", "4k3/8/8/3n4/4P3/8/8/4K3 w - - 0 1");
+        assert_eq!(reply, json!({ "quiet": false }));
+    }
+
+    #[test]
+    fn test_opening_reason() {
+        let mut session = Session::new();
+        let italian = "r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3";
+        let reply = query(&mut session, "opening", italian);
+        assert_eq!(reply, json!({ "opening": { "eco": "C50", "name": "Italian Game" } }));
+        assert_eq!(query(&mut session, "opening", START_FEN), json!({ "opening": null }));
+    }
+
+    #[test]
+    fn test_see_reason() {
+        let mut session = Session::new();
+        let see = |session: &mut Session, fen: &str, mv: &str| {
+            let line = format!(r#"{{"reason":"see","fen":"{}","moves":"{}"}}"#, fen, mv);
+            request(session, &line)
+        };
+        let fen = "1k6/2p5/3p4/4q3/3P4/8/8/1K6 w - - 0 1";
+        assert_eq!(see(&mut session, fen, "d4-e5"), json!({ "see": 800 }));
+        assert!(see(&mut session, fen, "d4").get("error").is_some());
+    }
+
+    #[test]
+    fn test_count_reason() {
+        let mut session = Session::new();
+        let reply = query(&mut session, "count", START_FEN);
+        assert_eq!(reply, json!({ "legal_move_count": 20 }));
+    }
+
+    #[test]
+    fn test_mobility_reason() {
+        let mut session = Session::new();
+        let reply = query(&mut session, "mobility", START_FEN);
+        assert_eq!(reply, json!({ "mobility": { "white": 20, "black": 20 } }));
+    }
+
+    #[test]
+    fn test_matein1_reason() {
+        let mut session = Session::new();
+        let reply = query(&mut session, "matein1", "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        assert_eq!(reply, json!({ "mate_in_one": ["a1-a8"] }));
+        let reply = query(&mut session, "matein1", START_FEN);
+        assert_eq!(reply, json!({ "mate_in_one": [] }));
+    }
+
+    #[test]
+    fn test_bestmove_reason() {
+        let mut session = Session::new();
+        let reply = query(&mut session, "bestmove", "4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1");
+        assert_ne!(reply["bestmove"], "d1-d5");
+        assert!(reply["score"].is_i64());
+
+        let reply = query(&mut session, "bestmove", "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        assert_eq!(reply["bestmove"], "a1-a8");
+
+        let reply = query(&mut session, "bestmove", "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(reply, json!({ "bestmove": null, "score": null }));
+    }
+
+    #[test]
+    fn test_new_game() {
+        let mut session = Session::new();
+        // Any supplied FEN is ignored
+        let reply = request(
+            &mut session,
+            r#"{"reason":"new","fen":"4k3/8/8/8/8/8/8/4K3 w - - 0 1"}"#,
+        );
+        assert_eq!(reply["fen"], START_FEN);
+        assert_eq!(reply["possible_moves"].as_array().unwrap().len(), 20);
+        assert_eq!(reply["repetition_count"], 1);
     }
 }
@@ -0,0 +1,113 @@
+/*
+ * Author: Renier Barnard
+ * A small embedded table of ECO openings, looked up by position
+ *
+ * Each line is replayed from the starting position once, and the position it reaches
+ * is keyed by its Zobrist hash. Looking a position up by hash means transpositions are
+ * recognised no matter which move order reached them.
+ */
+
+use crate::parsers::{fen_parser::Gamestate, san::san_to_move};
+use crate::validation::make_move::apply_move;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// ECO code, name and SAN moves of each known opening
+const OPENINGS: [(&str, &str, &str); 30] = [
+    ("B00", "King's Pawn Opening", "e4"),
+    ("C20", "King's Pawn Game", "e4 e5"),
+    ("C40", "King's Knight Opening", "e4 e5 Nf3"),
+    ("C44", "King's Pawn Game", "e4 e5 Nf3 Nc6"),
+    ("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+    ("C50", "Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5"),
+    ("C55", "Two Knights Defence", "e4 e5 Nf3 Nc6 Bc4 Nf6"),
+    ("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+    ("C45", "Scotch Game", "e4 e5 Nf3 Nc6 d4"),
+    ("C42", "Petrov's Defence", "e4 e5 Nf3 Nf6"),
+    ("C41", "Philidor Defence", "e4 e5 Nf3 d6"),
+    ("C30", "King's Gambit", "e4 e5 f4"),
+    ("C25", "Vienna Game", "e4 e5 Nc3"),
+    ("B20", "Sicilian Defence", "e4 c5"),
+    ("C00", "French Defence", "e4 e6"),
+    ("B10", "Caro-Kann Defence", "e4 c6"),
+    ("B01", "Scandinavian Defence", "e4 d5"),
+    ("B02", "Alekhine's Defence", "e4 Nf6"),
+    ("A40", "Queen's Pawn Opening", "d4"),
+    ("D00", "Queen's Pawn Game", "d4 d5"),
+    ("D06", "Queen's Gambit", "d4 d5 c4"),
+    ("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+    ("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+    ("D10", "Slav Defence", "d4 d5 c4 c6"),
+    ("A45", "Indian Defence", "d4 Nf6"),
+    ("E60", "King's Indian Defence", "d4 Nf6 c4 g6"),
+    ("E20", "Nimzo-Indian Defence", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    ("A80", "Dutch Defence", "d4 f5"),
+    ("A10", "English Opening", "c4"),
+    ("A04", "Zukertort Opening", "Nf3"),
+];
+
+/// A recognised opening
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Opening {
+    pub eco: &'static str,
+    pub name: &'static str,
+}
+
+/// The position key used for lookups. `position_hash` already skips an en passant
+/// square no pawn can take on, so it doesn't matter whether a client sends the target
+/// of such a double step.
+fn key(game: &Gamestate) -> u64 {
+    game.position_hash()
+}
+
+fn table() -> &'static HashMap<u64, Opening> {
+    static TABLE: OnceLock<HashMap<u64, Opening>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        OPENINGS
+            .iter()
+            .map(|&(eco, name, line)| {
+                let mut game = Gamestate::starting_position();
+                for san in line.split_whitespace() {
+                    let mv = san_to_move(&game, san)
+                        .unwrap_or_else(|e| panic!("bad opening line {}: {}", eco, e));
+                    apply_move(&mut game, &mv);
+                }
+                (key(&game), Opening { eco, name })
+            })
+            .collect()
+    })
+}
+
+/// Returns the opening whose main line reaches `game`'s position, or `None` for a
+/// position the table doesn't know
+pub fn identify(game: &Gamestate) -> Option<Opening> {
+    table().get(&key(game)).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_identify() {
+        // 1. e4 e5 2. Nf3
+        let game =
+            Gamestate::from_str("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2")
+                .unwrap();
+        let opening = identify(&game).unwrap();
+        assert_eq!(opening.eco, "C40");
+        assert_eq!(opening.name, "King's Knight Opening");
+
+        // 1. e4, with and without the e3 square no black pawn can take on
+        for ep in ["e3", "-"] {
+            let fen = format!("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq {} 0 1", ep);
+            let opening = identify(&Gamestate::from_str(&fen).unwrap()).unwrap();
+            assert_eq!(opening.eco, "B00", "{}", ep);
+        }
+
+        assert!(identify(&Gamestate::starting_position()).is_none());
+        assert_eq!(table().len(), OPENINGS.len());
+    }
+}
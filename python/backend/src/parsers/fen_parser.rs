@@ -1,7 +1,59 @@
+use crate::validation::board_validation::{validate_board, validate_position, BoardError};
+use crate::validation::pieces::{
+    king::castle_path_clear, occupancy, occupied_squares, piece_count, square_bit, Color, Piece,
+};
+use crate::validation::make_move::make_move;
+use crate::validation::possible_moves::{en_passant_exposes_king, CoordPromotionMove};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Non-king material (in centipawns) at or below which a side is considered to be in the endgame
+const ENDGAME_MATERIAL_THRESHOLD: i32 = 1300;
+
+/// The castling rights of a position by name, instead of by slot in `Gamestate::castling`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CastlingRights {
+    pub white_king: bool,
+    pub white_queen: bool,
+    pub black_king: bool,
+    pub black_queen: bool,
+}
+
+impl CastlingRights {
+    /// The castling field of a FEN: `KQkq` or a subset of it, or `-` with no rights left
+    pub fn to_fen_field(&self) -> String {
+        let field: String = [
+            (self.white_king, 'K'),
+            (self.white_queen, 'Q'),
+            (self.black_king, 'k'),
+            (self.black_queen, 'q'),
+        ]
+        .into_iter()
+        .filter(|&(right, _)| right)
+        .map(|(_, c)| c)
+        .collect();
+        if field.is_empty() {
+            "-".to_string()
+        } else {
+            field
+        }
+    }
+}
+
+/// The parts of a position that decide whether it repeats: board, side to move,
+/// castling rights and en passant square
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PositionKey {
+    pub board: [[char; 8]; 8],
+    pub player: char,
+    pub castling: (char, char, char, char),
+    pub enpassat: Option<(u8, u8)>,
+}
+
+/// Equality and `Hash` cover every field, move clocks included, so a `Gamestate` is
+/// not a repetition key: count repetitions by `position_key` instead.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Gamestate {
     pub board: [[char; 8]; 8],
     pub player: char,
@@ -22,7 +74,71 @@ impl Gamestate {
         }
     }
 
-    pub fn to_fen(self: &Self) -> String {
+    /// Returns the standard chess starting position
+    pub fn starting_position() -> Self {
+        Self::from_back_rank(['r', 'n', 'b', 'q', 'k', 'b', 'n', 'r'])
+    }
+
+    /// Builds a starting position with the given black back rank (files a-h), mirrored
+    /// for white, full pawn ranks and all castling rights
+    fn from_back_rank(back_rank: [char; 8]) -> Self {
+        let mut board = [[' '; 8]; 8];
+        for file in 0..8 {
+            board[0][file] = back_rank[file];
+            board[1][file] = 'p';
+            board[6][file] = 'P';
+            board[7][file] = back_rank[file].to_ascii_uppercase();
+        }
+
+        // Both rooks keep their castling rights, named in X-FEN
+        let king_file = back_rank.iter().position(|&p| p == 'k').unwrap_or(4);
+        let rooks: Vec<usize> = (0..8).filter(|&f| back_rank[f] == 'r').collect();
+        let letter = |rook_file: Option<&usize>| match rook_file {
+            Some(&file) => castling_letter(&board[7], 'R', king_file, file),
+            None => '-',
+        };
+        let (king_rook, queen_rook) = (letter(rooks.last()), letter(rooks.first()));
+
+        Gamestate {
+            board,
+            player: 'w',
+            castling: (
+                king_rook,
+                queen_rook,
+                king_rook.to_ascii_lowercase(),
+                queen_rook.to_ascii_lowercase(),
+            ),
+            enpassat: None,
+            halfmove: 0,
+            fullmove: 1,
+        }
+    }
+
+    /// Like `to_fen`, but the en passant square is only written when a pawn can legally
+    /// capture there. `to_fen` still writes it when the only capturers are pinned.
+    /// Castling rights are ordered `KQkq` in both.
+    pub fn to_fen_strict(&self) -> String {
+        if self.enpassat.is_some() && self.en_passant_capturers().is_empty() {
+            Gamestate {
+                enpassat: None,
+                ..self.clone()
+            }
+            .to_fen()
+        } else {
+            self.to_fen()
+        }
+    }
+
+    /// The first four FEN fields (board, side to move, castling, en passant) without the
+    /// move clocks, as a string key for opening books and transposition tables. The en
+    /// passant square follows `to_fen_strict`, so move orders that differ only in an
+    /// uncapturable double step still give the same key.
+    pub fn to_fen_position(&self) -> String {
+        let fen = self.to_fen_strict();
+        fen.split(' ').take(4).collect::<Vec<_>>().join(" ")
+    }
+
+    pub fn to_fen(&self) -> String {
         let mut fen = String::new();
         for rank in 0usize..8 {
             let mut empty: u8 = 0;
@@ -51,30 +167,15 @@ impl Gamestate {
         fen.push(' ');
         fen.push(self.player);
 
-        // Castling rights
+        // Castling rights, keeping any X-FEN rook files
         fen.push(' ');
-        let (wk, wq, bk, bq) = self.castling;
-        let mut castling = String::new();
-        if wk != '-' {
-            castling.push('K');
-        }
-        if wq != '-' {
-            castling.push('Q');
-        }
-        if bk != '-' {
-            castling.push('k');
-        }
-        if bq != '-' {
-            castling.push('q');
-        }
-        if castling.is_empty() {
-            castling.push('-');
-        }
-        fen.push_str(&castling);
+        let (k, q, bk, bq) = self.castling;
+        let castling: String = [k, q, bk, bq].into_iter().filter(|&c| c != '-').collect();
+        fen.push_str(if castling.is_empty() { "-" } else { &castling });
 
-        // En passant target
+        // En passant target, only when an enemy pawn stands ready to take on it
         fen.push(' ');
-        if let Some((row, file)) = self.enpassat {
+        if let Some((row, file)) = self.en_passant_square() {
             // Convert board coordinates (row, col) back to chess notation
             // row 0 = rank 8, row 7 = rank 1
             fen.push((b'a' + file) as char);
@@ -91,26 +192,403 @@ impl Gamestate {
 
         fen
     }
+
+    /// Returns the structured JSON form of the position: the board as 8 rows of
+    /// one-character strings (rank 8 first), plus the other fields by name
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).expect("Gamestate always serializes")
+    }
+
+    /// Reads a position from the structured JSON form produced by `to_json`
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        Gamestate::deserialize(value).map_err(|e| format!("invalid game state: {}", e))
+    }
+
+    /// Returns the board as a 64-character string, row 0 (rank 8) first, with a space
+    /// for each empty square
+    pub fn board_string(&self) -> String {
+        self.board.iter().flatten().collect()
+    }
+
+    /// Replaces the board with one in the form of `board_string`. The board is left
+    /// unchanged if the string is not 64 piece characters and spaces.
+    pub fn set_board_string(&mut self, board: &str) -> Result<(), String> {
+        let squares: Vec<char> = board.chars().collect();
+        if squares.len() != 64 {
+            return Err(format!("Board string has {} squares, expected 64", squares.len()));
+        }
+        if let Some(&c) = squares.iter().find(|&&c| c != ' ' && Piece::from_char(c).is_none()) {
+            return Err(format!("Invalid piece character: '{}'", c));
+        }
+        for (i, &c) in squares.iter().enumerate() {
+            self.board[i / 8][i % 8] = c;
+        }
+        Ok(())
+    }
+
+    /// Zobrist hash of the parts of the position that matter for repetition: the board,
+    /// the side to move, castling rights and the en passant square. The move clocks are ignored.
+    pub fn position_hash(&self) -> u64 {
+        crate::zobrist::hash(self)
+    }
+
+    /// The position without its move clocks, for storing positions in a map when
+    /// counting repetitions
+    pub fn position_key(&self) -> PositionKey {
+        PositionKey {
+            board: self.board,
+            player: self.player,
+            castling: self.castling,
+            enpassat: self.enpassat,
+        }
+    }
+
+    /// Returns true when the position can be treated as an endgame.
+    ///
+    /// That is either when neither side has a queen, or when both sides have
+    /// at most 13 points of non-king material.
+    pub fn is_endgame(&self) -> bool {
+        let no_queens = piece_count(&self.board, Piece::Queen, Color::White) == 0
+            && piece_count(&self.board, Piece::Queen, Color::Black) == 0;
+
+        let material = |color: Color| -> i32 {
+            Piece::ALL
+                .iter()
+                .map(|&piece| piece_count(&self.board, piece, color) as i32 * piece.value())
+                .sum()
+        };
+
+        no_queens
+            || (material(Color::White) <= ENDGAME_MATERIAL_THRESHOLD
+                && material(Color::Black) <= ENDGAME_MATERIAL_THRESHOLD)
+    }
+
+    /// Returns the piece on `square` (`(row, col)`, row 0 being rank 8), or `None` for
+    /// an empty or off-board square
+    pub fn piece_at(&self, square: (u8, u8)) -> Option<char> {
+        let piece = *self.board.get(square.0 as usize)?.get(square.1 as usize)?;
+        (piece != ' ').then_some(piece)
+    }
+
+    /// Returns the color of the piece on `square`, or `None` for an empty or off-board square
+    pub fn color_at(&self, square: (u8, u8)) -> Option<Color> {
+        self.piece_at(square).and_then(Color::of)
+    }
+
+    /// Places `piece` (a FEN piece character, e.g. `'N'` or `'q'`) on `square`, replacing
+    /// whatever stood there
+    pub fn set_piece(&mut self, square: (u8, u8), piece: char) -> Result<(), String> {
+        if Piece::from_char(piece).is_none() {
+            return Err(format!("Invalid piece character: '{}'", piece));
+        }
+        if square.0 > 7 || square.1 > 7 {
+            return Err(format!("Square out of range: {:?}", square));
+        }
+        self.board[square.0 as usize][square.1 as usize] = piece;
+        Ok(())
+    }
+
+    /// Like `set_piece`, but also requires the resulting board to pass `validate_board`.
+    /// The board is left unchanged when it doesn't.
+    pub fn set_piece_strict(&mut self, square: (u8, u8), piece: char) -> Result<(), String> {
+        let previous = self.board;
+        self.set_piece(square, piece)?;
+        validate_board(&self.board).inspect_err(|_| self.board = previous)
+    }
+
+    /// Empties `square`, returning the piece that stood there
+    pub fn remove_piece(&mut self, square: (u8, u8)) -> Option<char> {
+        let piece = self.piece_at(square)?;
+        self.board[square.0 as usize][square.1 as usize] = ' ';
+        Some(piece)
+    }
+
+    /// Iterates over every occupied square and the piece standing on it
+    pub fn pieces(&self) -> impl Iterator<Item = ((u8, u8), char)> + '_ {
+        occupied_squares(&self.board)
+    }
+
+    /// Iterates over the squares and pieces of one color
+    pub fn pieces_of(&self, color: Color) -> impl Iterator<Item = ((u8, u8), char)> + '_ {
+        self.pieces().filter(move |&(_, piece)| Color::of(piece) == Some(color))
+    }
+
+    /// Bitset of the occupied squares; bit `row * 8 + col` is set for each piece, with
+    /// row 0 being rank 8 (see `square_bit`)
+    pub fn occupancy(&self) -> u64 {
+        occupancy(&self.board)
+    }
+
+    /// Bitset of the squares holding pieces of `color`, numbered as in `occupancy`
+    pub fn color_occupancy(&self, color: Color) -> u64 {
+        self.pieces_of(color)
+            .fold(0, |bits, (square, _)| bits | square_bit(square))
+    }
+
+    /// True if nothing stands on `square`. Off-board squares are not empty.
+    pub fn is_square_empty(&self, square: (u8, u8)) -> bool {
+        square.0 < 8 && square.1 < 8 && self.piece_at(square).is_none()
+    }
+
+    /// Plays a sequence of `(from, to, promotion)` moves, e.g. an opening line, and returns
+    /// the position reached. `self` is left as it is. On the first illegal move, returns
+    /// its index in `moves` and the reason from `make_move`.
+    pub fn with_moves(
+        &self,
+        moves: &[CoordPromotionMove],
+    ) -> Result<Gamestate, (usize, String)> {
+        moves
+            .iter()
+            .enumerate()
+            .try_fold(self.clone(), |game, (i, &(from, to, promotion))| {
+                make_move(&game, from, to, promotion).map_err(|e| (i, e))
+            })
+    }
+
+    /// Hands the turn to the other side without a move being played (a "null move"):
+    /// swaps `player`, clears the en passant square and advances `fullmove` when Black
+    /// passes. The halfmove clock is left alone.
+    ///
+    /// The result may not be a legal position (the side that passed can be left giving
+    /// check), so this is for analysis such as null-move search only.
+    pub fn pass_turn(&mut self) {
+        if self.player == 'b' {
+            self.fullmove += 1;
+        }
+        self.player = if self.player == 'w' { 'b' } else { 'w' };
+        self.enpassat = None;
+    }
+
+    /// Returns the color-mirrored position: the board flipped top to bottom with the
+    /// piece colors swapped, castling rights and en passant square mirrored to match,
+    /// and the other side to move. The move clocks are kept.
+    pub fn mirror(&self) -> Gamestate {
+        let mut board = [[' '; 8]; 8];
+        for (rank, row) in self.board.iter().enumerate() {
+            for (file, &piece) in row.iter().enumerate() {
+                board[7 - rank][file] = if piece.is_uppercase() {
+                    piece.to_ascii_lowercase()
+                } else {
+                    piece.to_ascii_uppercase()
+                };
+            }
+        }
+
+        let (k, q, bk, bq) = self.castling;
+        Gamestate {
+            board,
+            player: if self.player == 'w' { 'b' } else { 'w' },
+            castling: (
+                bk.to_ascii_uppercase(),
+                bq.to_ascii_uppercase(),
+                k.to_ascii_lowercase(),
+                q.to_ascii_lowercase(),
+            ),
+            enpassat: self.enpassat.map(|(rank, file)| (7 - rank, file)),
+            halfmove: self.halfmove,
+            fullmove: self.fullmove,
+        }
+    }
+
+    /// Parses `fen` like `from_str`, then requires the position to pass
+    /// `is_legal_position`
+    pub fn from_fen_strict(fen: &str) -> Result<Self, String> {
+        let game = Gamestate::from_str(fen)?;
+        game.is_legal_position().map_err(|e| e.to_string())?;
+        Ok(game)
+    }
+
+    /// Runs every legality check on the position: the board itself, the side not to
+    /// move not being in check, en passant and castling rights (see `validate_position`)
+    pub fn is_legal_position(&self) -> Result<(), BoardError> {
+        validate_position(self)
+    }
+
+    /// Returns the castling rights still held, by side and wing
+    pub fn castling_rights(&self) -> CastlingRights {
+        let (wk, wq, bk, bq) = self.castling;
+        CastlingRights {
+            white_king: wk != '-',
+            white_queen: wq != '-',
+            black_king: bk != '-',
+            black_queen: bq != '-',
+        }
+    }
+
+    /// Returns the square of the rook that `color`'s castling right on the given side
+    /// names, or None when the right is not held or the king or that rook is no longer
+    /// on the back rank. `K` and `Q` name the outermost rook on their side of the king,
+    /// an X-FEN file letter the rook on that file.
+    pub fn castling_rook(&self, color: Color, kingside: bool) -> Option<(u8, u8)> {
+        let (k, q, bk, bq) = self.castling;
+        let right = match (color, kingside) {
+            (Color::White, true) => k,
+            (Color::White, false) => q,
+            (Color::Black, true) => bk,
+            (Color::Black, false) => bq,
+        };
+        if right == '-' {
+            return None;
+        }
+
+        let rank = if color == Color::White { 7 } else { 0 };
+        let back_rank = &self.board[rank];
+        let king = Piece::King.to_char(color);
+        let king_file = back_rank.iter().position(|&p| p == king)?;
+        let rook = Piece::Rook.to_char(color);
+        castling_rook_file(back_rank, rook, king_file, right)
+            .filter(|&file| (file > king_file) == kingside)
+            .map(|file| (rank as u8, file as u8))
+    }
+
+    /// Returns true when `color` still holds the castling right on the given side
+    /// and the king and rook are in place with nothing between them.
+    ///
+    /// This does not consider check: the king may still be in check, or pass
+    /// through or land on an attacked square.
+    pub fn can_castle(&self, color: Color, kingside: bool) -> bool {
+        castle_path_clear(&self.board, self.castling, color, kingside)
+    }
+
+    /// Returns the squares of the side to move's pawns that can legally capture en passant.
+    ///
+    /// `enpassat` being set only means a pawn just double-stepped; this also requires a
+    /// pawn beside it and rejects captures that would expose the king (including the
+    /// case where both pawns leave the king's rank).
+    pub fn en_passant_capturers(&self) -> Vec<(u8, u8)> {
+        let Some(target) = self.enpassat else {
+            return Vec::new();
+        };
+        self.en_passant_pawns()
+            .into_iter()
+            .filter(|&from| !en_passant_exposes_king(&self.board, from, target))
+            .collect()
+    }
+
+    /// The en passant square as `to_fen` writes it: `enpassat`, but only while a pawn
+    /// of the side to move stands beside the pawn that double-stepped
+    pub fn en_passant_square(&self) -> Option<(u8, u8)> {
+        self.enpassat.filter(|_| !self.en_passant_pawns().is_empty())
+    }
+
+    /// The side to move's pawns beside the pawn that just double-stepped, whether or
+    /// not taking en passant would leave their king in check
+    fn en_passant_pawns(&self) -> Vec<(u8, u8)> {
+        let Some((row, file)) = self.enpassat else {
+            return Vec::new();
+        };
+        let color = Color::from_player(self.player);
+
+        // The pawn that double-stepped sits behind the target square; capturers sit beside it
+        let pawn_row = match (color, row) {
+            (Color::White, 2) => 3u8,
+            (Color::Black, 5) => 4u8,
+            _ => return Vec::new(),
+        };
+        if self.board[row as usize][file as usize] != ' '
+            || self.board[pawn_row as usize][file as usize] != Piece::Pawn.to_char(color.opposite())
+        {
+            return Vec::new();
+        }
+
+        [file.checked_sub(1), Some(file + 1)]
+            .into_iter()
+            .flatten()
+            .filter(|&f| f < 8)
+            .map(|f| (pawn_row, f))
+            .filter(|&(r, f)| self.board[r as usize][f as usize] == Piece::Pawn.to_char(color))
+            .collect()
+    }
+}
+
+/// Knight placements over the five squares left after the bishops and queen are placed
+const CHESS960_KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+/// Returns the Chess960 starting position with the standard number `id` (0-959).
+///
+/// Uses the standard derivation: the light-squared bishop, the dark-squared bishop,
+/// the queen and the knights are placed in turn, then a rook, the king and the other
+/// rook fill the three remaining squares so the king always sits between the rooks.
+/// Id 518 is the standard chess starting position; ids above 959 wrap around.
+///
+/// Castling rights are written in X-FEN, which for a starting position with its two
+/// rooks is always `KQkq`. Castling itself is only generated when the king and rooks
+/// stand on their standard squares.
+pub fn chess960_start(id: u16) -> Gamestate {
+    let mut n = (id % 960) as usize;
+    let mut back_rank: [Option<char>; 8] = [None; 8];
+
+    // Light-squared bishop on b, d, f or h, then dark-squared on a, c, e or g
+    back_rank[(n % 4) * 2 + 1] = Some('b');
+    n /= 4;
+    back_rank[(n % 4) * 2] = Some('b');
+    n /= 4;
+
+    // Fills the `index`th still-empty square with `piece`
+    let place = |back_rank: &mut [Option<char>; 8], index: usize, piece: char| {
+        if let Some(square) = back_rank.iter_mut().filter(|s| s.is_none()).nth(index) {
+            *square = Some(piece);
+        }
+    };
+
+    place(&mut back_rank, n % 6, 'q');
+    n /= 6;
+
+    // Place the second knight first so the first knight's index is unaffected
+    let (first, second) = CHESS960_KNIGHTS[n];
+    place(&mut back_rank, second, 'n');
+    place(&mut back_rank, first, 'n');
+
+    for piece in ['r', 'k', 'r'] {
+        place(&mut back_rank, 0, piece);
+    }
+
+    Gamestate::from_back_rank(back_rank.map(|square| square.unwrap_or(' ')))
+}
+
+impl Default for Gamestate {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FromStr for Gamestate {
     type Err = String;
 
+    /// Parses a full 6-field FEN, or a 4-field one without the move clocks, which then
+    /// default to halfmove 0 and fullmove 1
     fn from_str(fen: &str) -> Result<Self, Self::Err> {
         let parts: Vec<&str> = fen.split_whitespace().collect();
-        if parts.len() != 6 {
+        if parts.len() != 6 && parts.len() != 4 {
             return Err("Invalid FEN format".to_string());
         }
 
         let board: [[char; 8]; 8] = parse_board(parts[0])?;
-        let player: char = parts[1].chars().next().ok_or("Invalid player turn")?;
-        let castling: (char, char, char, char) = parse_castling(parts[2]);
+        let player: char = match parts[1] {
+            "w" => 'w',
+            "b" => 'b',
+            _ => return Err("Invalid side to move".to_string()),
+        };
+        let castling: (char, char, char, char) = parse_castling(parts[2], &board)?;
         let enpassant: Option<(u8, u8)> = parse_enpassant(parts[3])?;
-        let halfmove: u8 = parts[4]
-            .parse::<u8>()
+        let halfmove: u8 = parts
+            .get(4)
+            .map_or(Ok(0), |clock| clock.parse::<u8>())
             .map_err(|_| "Invalid halfmove clock")?;
-        let fullmove: u16 = parts[5]
-            .parse::<u16>()
+        let fullmove: u16 = parts
+            .get(5)
+            .map_or(Ok(1), |number| number.parse::<u16>())
             .map_err(|_| "Invalid fullmove number")?;
 
         Ok(Gamestate {
@@ -139,7 +617,7 @@ fn parse_board(board_str: &str) -> Result<[[char; 8]; 8], String> {
         // So FEN rank 8 -> board[0], rank 1 -> board[7]
         let board_row = i;
         for c in row.chars() {
-            if c.is_digit(10) {
+            if c.is_ascii_digit() {
                 col += c.to_digit(10).unwrap() as usize;
             } else if "prnbqkPRNBQK".contains(c) {
                 if col >= 8 {
@@ -159,23 +637,83 @@ fn parse_board(board_str: &str) -> Result<[[char; 8]; 8], String> {
     Ok(board)
 }
 
-fn parse_castling(castling_str: &str) -> (char, char, char, char) {
+/// Reads the castling field: `-`, or at most one right per side and wing. A right is
+/// `K`, `Q`, `k` or `q`, or an X-FEN rook file (`A`-`H`, `a`-`h`) whose wing follows
+/// from which side of the king on `board` the file is. A file holding the outermost
+/// rook on its wing is stored as `K`/`Q`/`k`/`q`, so Shredder-FEN `HAha` reads as `KQkq`;
+/// only an inner rook keeps its file letter, and castling with it is not generated.
+fn parse_castling(
+    castling_str: &str,
+    board: &[[char; 8]; 8],
+) -> Result<(char, char, char, char), String> {
     let mut castling: (char, char, char, char) = ('-', '-', '-', '-');
-    if castling_str != "-" {
-        if castling_str.contains('K') {
-            castling.0 = 'K';
-        }
-        if castling_str.contains('Q') {
-            castling.1 = 'Q';
-        }
-        if castling_str.contains('k') {
-            castling.2 = 'k';
+    if castling_str == "-" {
+        return Ok(castling);
+    }
+    for c in castling_str.chars() {
+        let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+        let (kingside, right) = match c.to_ascii_uppercase() {
+            'K' => (true, c),
+            'Q' => (false, c),
+            letter @ 'A'..='H' => {
+                let rank = if color == Color::White { 7 } else { 0 };
+                let king = Piece::King.to_char(color);
+                let king_file = board[rank]
+                    .iter()
+                    .position(|&p| p == king)
+                    .ok_or_else(|| format!("Castling file '{}' without a king on its rank", c))?;
+                // The outermost rook's file is stored as plain K or Q, which is what
+                // move generation and apply_move look for
+                let file = (letter as u8 - b'A') as usize;
+                let rook = Piece::Rook.to_char(color);
+                let right = castling_letter(&board[rank], rook, king_file, file);
+                let right = if color == Color::White { right } else { right.to_ascii_lowercase() };
+                (file > king_file, right)
+            }
+            _ => return Err(format!("Invalid castling character: '{}'", c)),
+        };
+        let slot = match (color, kingside) {
+            (Color::White, true) => &mut castling.0,
+            (Color::White, false) => &mut castling.1,
+            (Color::Black, true) => &mut castling.2,
+            (Color::Black, false) => &mut castling.3,
+        };
+        if *slot != '-' {
+            return Err(format!("Duplicate castling right: '{}'", c));
         }
-        if castling_str.contains('q') {
-            castling.3 = 'q';
+        *slot = right;
+    }
+    Ok(castling)
+}
+
+/// The file of the rook a castling right names on `back_rank`, with the king on
+/// `king_file`: the outermost `rook` on that side for `K` or `Q` (either case),
+/// otherwise the rook on the right's X-FEN file
+fn castling_rook_file(
+    back_rank: &[char; 8],
+    rook: char,
+    king_file: usize,
+    right: char,
+) -> Option<usize> {
+    match right.to_ascii_uppercase() {
+        'K' => (king_file + 1..8).rev().find(|&f| back_rank[f] == rook),
+        'Q' => (0..king_file).find(|&f| back_rank[f] == rook),
+        letter @ 'A'..='H' => {
+            Some((letter as u8 - b'A') as usize).filter(|&f| back_rank[f] == rook)
         }
+        _ => None,
+    }
+}
+
+/// The uppercase X-FEN letter for castling with the rook on `rook_file`: `K` or `Q`
+/// when it is the outermost `rook` on its side of the king, otherwise its file letter
+fn castling_letter(back_rank: &[char; 8], rook: char, king_file: usize, rook_file: usize) -> char {
+    let outermost = if rook_file > king_file { 'K' } else { 'Q' };
+    if castling_rook_file(back_rank, rook, king_file, outermost) == Some(rook_file) {
+        outermost
+    } else {
+        (b'A' + rook_file as u8) as char
     }
-    castling
 }
 
 fn parse_enpassant(ep_str: &str) -> Result<Option<(u8, u8)>, String> {
@@ -190,15 +728,478 @@ fn parse_enpassant(ep_str: &str) -> Result<Option<(u8, u8)>, String> {
     // Convert chess notation to board coordinates
     // Example: "e3" -> file='e' (column 4), rank='3'
     // Board uses (row, col) where row 0 = rank 8, row 7 = rank 1
-    let file: u8 = chars[0] as u8 - b'a';  // Column: a=0, b=1, ..., h=7
-    let rank_num: u8 = chars[1].to_digit(10).ok_or("Invalid en passant rank")? as u8;
-    
-    if file > 7 || rank_num < 1 || rank_num > 8 {
-        return Err("En passant square out of bounds".to_string());
-    }
+    // Column: a=0, b=1, ..., h=7. The chars are matched before any `as u8`, which would
+    // keep only the low byte and read e.g. 'ť' (U+0165) as 'e'
+    let file: u8 = match chars[0] {
+        c @ 'a'..='h' => c as u8 - b'a',
+        _ => return Err("Invalid en passant file".to_string()),
+    };
+    let rank_num: u8 = match chars[1] {
+        c @ '1'..='8' => c as u8 - b'0',
+        _ => return Err("Invalid en passant rank".to_string()),
+    };
     
     // Convert rank number to row index: rank 8 -> row 0, rank 1 -> row 7
     let row: u8 = 8 - rank_num;
     
     Ok(Some((row, file)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn test_starting_position() {
+        assert_eq!(Gamestate::starting_position().to_fen(), START_FEN);
+    }
+
+    #[test]
+    fn test_position_key_ignores_clocks() {
+        let start = Gamestate::starting_position();
+        let later = Gamestate::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 8 5")
+            .unwrap();
+        assert_ne!(start, later);
+        assert_eq!(start.position_key(), later.position_key());
+        assert_eq!(start, Gamestate::starting_position());
+
+        let mut counts: std::collections::HashMap<_, u8> = std::collections::HashMap::new();
+        for game in [&start, &later, &Gamestate::new()] {
+            *counts.entry(game.position_key()).or_default() += 1;
+        }
+        assert_eq!(counts[&start.position_key()], 2);
+        assert_eq!(counts.len(), 2);
+
+        // As a key itself, a position with other clocks is a different game state
+        let states: std::collections::HashSet<_> = [&start, &later].into_iter().collect();
+        assert_eq!(states.len(), 2);
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        let fens = [
+            START_FEN,
+            // Kiwipete
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            // Promotions for both sides, with and without captures
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            // En passant for White on f6 and for Black on d3
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "rnbqkbnr/pppp1ppp/8/8/3Pp3/2N2N2/PPP1PPPP/R1BQKB1R b KQkq d3 0 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 11 42",
+        ];
+        for fen in fens {
+            let game = Gamestate::from_str(fen).unwrap();
+            assert_eq!(game.to_fen(), fen);
+            assert_eq!(Gamestate::from_str(&game.to_fen()).unwrap(), game);
+        }
+
+        let game = Gamestate::from_str(fens[4]).unwrap();
+        assert_eq!(game.enpassat, Some((2, 5)));
+        assert_eq!(game.piece_at((3, 4)), Some('P'));
+        let game = Gamestate::from_str(fens[5]).unwrap();
+        assert_eq!(game.enpassat, Some((5, 3)));
+    }
+
+    #[test]
+    fn test_rejects_bad_en_passant_file() {
+        // U+0165 and U+0133 have the low bytes of 'e' and '3'
+        for ep in ["E3", "33", "i3", "e9", "e0", "ť3", "e\u{133}"] {
+            let fen = START_FEN.replace(" - ", &format!(" {} ", ep));
+            assert!(Gamestate::from_str(&fen).is_err(), "{}", ep);
+        }
+    }
+
+    #[test]
+    fn test_parse_castling() {
+        let board = Gamestate::starting_position().board;
+        assert_eq!(parse_castling("-", &board), Ok(('-', '-', '-', '-')));
+        assert_eq!(parse_castling("KQkq", &board), Ok(('K', 'Q', 'k', 'q')));
+        assert_eq!(parse_castling("Kq", &board), Ok(('K', '-', '-', 'q')));
+        assert_eq!(parse_castling("kQ", &board), Ok(('-', 'Q', 'k', '-')));
+        // Files of the outermost rooks are the plain rights
+        assert_eq!(parse_castling("HAha", &board), Ok(('K', 'Q', 'k', 'q')));
+        assert_eq!(parse_castling("Ah", &board), Ok(('-', 'Q', 'k', '-')));
+
+        assert!(parse_castling("KKKK", &board).is_err());
+        assert!(parse_castling("KQkk", &board).is_err());
+        assert!(parse_castling("KQXk", &board).is_err());
+        assert!(parse_castling("K-", &board).is_err());
+        assert!(parse_castling("KH", &board).is_err());
+        assert!(parse_castling("A", &[[' '; 8]; 8]).is_err());
+        assert!(Gamestate::from_str(&START_FEN.replace("KQkq", "KQXk")).is_err());
+    }
+
+    #[test]
+    fn test_four_field_fen() {
+        let game = Gamestate::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -");
+        assert_eq!(game.unwrap().to_fen(), START_FEN);
+
+        // The clocks are only defaulted when both are missing
+        let later = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 7 12";
+        let game = Gamestate::from_str(later).unwrap();
+        assert_eq!((game.halfmove, game.fullmove), (7, 12));
+        assert!(Gamestate::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0")
+            .is_err());
+        assert!(Gamestate::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq").is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_side_to_move() {
+        for turn in ["x", "W", "white", "wb"] {
+            let fen = START_FEN.replace(" w ", &format!(" {} ", turn));
+            assert_eq!(Gamestate::from_str(&fen).unwrap_err(), "Invalid side to move");
+        }
+        let black = START_FEN.replace(" w ", " b ");
+        assert_eq!(Gamestate::from_str(&black).unwrap().player, 'b');
+    }
+
+    #[test]
+    fn test_piece_at() {
+        let game = Gamestate::starting_position();
+        assert_eq!(game.piece_at((7, 4)), Some('K'));
+        assert_eq!(game.color_at((0, 3)), Some(Color::Black));
+        assert_eq!(game.piece_at((4, 4)), None);
+        assert_eq!(game.color_at((4, 4)), None);
+        assert_eq!(game.piece_at((8, 0)), None);
+        assert_eq!(game.color_at((0, 200)), None);
+    }
+
+    #[test]
+    fn test_set_and_remove_piece() {
+        let mut game = Gamestate::new();
+        assert_eq!(game.set_piece((4, 4), 'N'), Ok(()));
+        assert_eq!(game.piece_at((4, 4)), Some('N'));
+        assert_eq!(game.remove_piece((4, 4)), Some('N'));
+        assert_eq!(game.piece_at((4, 4)), None);
+        assert_eq!(game.remove_piece((4, 4)), None);
+
+        assert!(game.set_piece((4, 4), 'x').is_err());
+        assert!(game.set_piece((4, 4), ' ').is_err());
+        assert!(game.set_piece((8, 4), 'N').is_err());
+        assert_eq!(game.pieces().count(), 0);
+    }
+
+    #[test]
+    fn test_set_piece_strict() {
+        let mut game = Gamestate::starting_position();
+        // A third king fails validation and is not placed
+        assert!(game.set_piece_strict((4, 4), 'K').is_err());
+        assert_eq!(game.piece_at((4, 4)), None);
+        // A third knight needs a pawn to have promoted
+        assert!(game.set_piece_strict((4, 4), 'N').is_err());
+        assert_eq!(game.set_piece_strict((6, 4), 'N'), Ok(()));
+        assert_eq!(game.piece_at((6, 4)), Some('N'));
+    }
+
+    #[test]
+    fn test_pieces() {
+        let game = Gamestate::starting_position();
+        assert_eq!(game.pieces().count(), 32);
+        assert_eq!(game.pieces_of(Color::White).count(), 16);
+        assert!(game
+            .pieces_of(Color::Black)
+            .all(|((rank, _), piece)| rank <= 1 && piece.is_lowercase()));
+        assert_eq!(game.pieces().next(), Some(((0, 0), 'r')));
+    }
+
+    #[test]
+    fn test_occupancy() {
+        let game = Gamestate::starting_position();
+        let occupied = game.occupancy();
+        assert_eq!(occupied.count_ones(), 32);
+        assert_eq!(occupied, 0xFFFF_0000_0000_FFFF);
+        assert_eq!(game.color_occupancy(Color::Black), 0xFFFF);
+        assert_eq!(game.color_occupancy(Color::White), 0xFFFF << 48);
+        assert_ne!(occupied & square_bit((7, 4)), 0); // e1
+        assert!(game.is_square_empty((4, 4)));
+        assert!(!game.is_square_empty((7, 4)));
+        assert!(!game.is_square_empty((8, 0)));
+    }
+
+    #[test]
+    fn test_with_moves() {
+        let start = Gamestate::starting_position();
+        // 1. e4 e5 2. Nf3 Nc6
+        let line = [
+            ((6, 4), (4, 4), None),
+            ((1, 4), (3, 4), None),
+            ((7, 6), (5, 5), None),
+            ((0, 1), (2, 2), None),
+        ];
+        let game = start.with_moves(&line).unwrap();
+        assert_eq!(
+            game.to_fen(),
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"
+        );
+        assert_eq!(start.to_fen(), Gamestate::starting_position().to_fen());
+
+        // The third move repeats a white move while it is Black's turn
+        let bad = [line[0], line[1], line[0]];
+        assert_eq!(start.with_moves(&bad).unwrap_err().0, 2);
+        assert_eq!(start.with_moves(&[]).unwrap().to_fen(), start.to_fen());
+    }
+
+    #[test]
+    fn test_board_string_round_trip() {
+        let game = Gamestate::starting_position();
+        let board = game.board_string();
+        assert_eq!(board.len(), 64);
+        assert!(board.starts_with("rnbqkbnrpppppppp "));
+        assert!(board.ends_with("PPPPPPPPRNBQKBNR"));
+
+        let mut copy = Gamestate::new();
+        copy.set_board_string(&board).unwrap();
+        assert_eq!(copy.board, game.board);
+
+        assert!(copy.set_board_string("rnbqkbnr").is_err());
+        assert!(copy.set_board_string(&board.replace('K', "X")).is_err());
+        assert_eq!(copy.board, game.board);
+    }
+
+    #[test]
+    fn test_from_fen_strict() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert!(Gamestate::from_fen_strict(start).is_ok());
+        // Parses, but Black is in check with White to move
+        let fen = "4k3/8/8/8/8/8/8/4RK2 w - - 0 1";
+        assert!(Gamestate::from_str(fen).is_ok());
+        assert_eq!(
+            Gamestate::from_fen_strict(fen).unwrap_err(),
+            "The side not to move is in check"
+        );
+    }
+
+    #[test]
+    fn test_castling_rights() {
+        let mut game = Gamestate::starting_position();
+        game.castling = ('K', '-', 'k', '-');
+        let rights = game.castling_rights();
+        assert_eq!(
+            rights,
+            CastlingRights {
+                white_king: true,
+                white_queen: false,
+                black_king: true,
+                black_queen: false,
+            }
+        );
+        assert_eq!(rights.to_fen_field(), "Kk");
+        assert_eq!(CastlingRights::default().to_fen_field(), "-");
+        assert_eq!(Gamestate::starting_position().castling_rights().to_fen_field(), "KQkq");
+    }
+
+    #[test]
+    fn test_pass_turn() {
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        let mut game = Gamestate::from_str(fen).unwrap();
+        game.pass_turn();
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+        game.pass_turn();
+        assert_eq!(game.player, 'b');
+        assert_eq!(game.fullmove, 2);
+        assert_eq!(game.board, Gamestate::from_str(fen).unwrap().board);
+    }
+
+    #[test]
+    fn test_mirror() {
+        let game = Gamestate::from_str("r3k2r/8/8/3pP3/8/8/8/R3K3 w Qkq d6 0 5").unwrap();
+        assert_eq!(game.mirror().to_fen(), "r3k3/8/8/8/3Pp3/8/8/R3K2R b KQq d3 0 5");
+        assert_eq!(game.mirror().mirror().to_fen(), game.to_fen());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let value = serde_json::json!({
+            "board": [
+                ["", "", "", "", "k", "", "", ""],
+                [" ", " ", " ", " ", " ", " ", " ", " "],
+                [" ", " ", " ", " ", " ", " ", " ", " "],
+                [" ", " ", " ", "p", "P", " ", " ", " "],
+                [" ", " ", " ", " ", " ", " ", " ", " "],
+                [" ", " ", " ", " ", " ", " ", " ", " "],
+                [" ", " ", " ", " ", " ", " ", " ", " "],
+                [" ", " ", " ", " ", "K", " ", " ", " "]
+            ],
+            "player": "w",
+            "castling": ["-", "-", "-", "-"],
+            "enpassat": [2, 3],
+            "halfmove": 0,
+            "fullmove": 2
+        });
+        assert!(Gamestate::from_json(&value).is_err()); // "" is not a char
+
+        let mut value = value;
+        value["board"][0] = serde_json::json!([" ", " ", " ", " ", "k", " ", " ", " "]);
+        let game = Gamestate::from_json(&value).unwrap();
+        assert_eq!(game.to_fen(), "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2");
+        assert_eq!(game.to_json(), value);
+    }
+
+    #[test]
+    fn test_chess960_standard_position() {
+        assert_eq!(chess960_start(518).to_fen(), START_FEN);
+        assert_eq!(
+            chess960_start(0).to_fen(),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1"
+        );
+    }
+
+    #[test]
+    fn test_chess960_positions_are_valid() {
+        let mut seen = std::collections::HashSet::new();
+        for id in 0..960 {
+            let back_rank = chess960_start(id).board[0];
+            let files = |piece: char| -> Vec<usize> {
+                (0..8).filter(|&f| back_rank[f] == piece).collect()
+            };
+
+            let bishops = files('b');
+            assert_eq!(bishops.len(), 2);
+            assert_ne!(bishops[0] % 2, bishops[1] % 2, "bishops share a color in {}", id);
+
+            let rooks = files('r');
+            let king = files('k')[0];
+            assert!(rooks[0] < king && king < rooks[1], "king not between rooks in {}", id);
+
+            assert_eq!(files('q').len(), 1);
+            assert_eq!(files('n').len(), 2);
+            assert!(seen.insert(back_rank));
+        }
+    }
+
+    #[test]
+    fn test_chess960_positions_are_legal() {
+        for id in 0..960 {
+            let game = chess960_start(id);
+            assert!(game.is_legal_position().is_ok(), "{}", game.to_fen());
+            assert!(game.castling_rook(Color::White, true).is_some(), "{}", id);
+            assert!(game.castling_rook(Color::Black, false).is_some(), "{}", id);
+        }
+    }
+
+    #[test]
+    fn test_xfen_castling_file() {
+        // Two rooks on the kingside: F names the inner one, K the outer one
+        let fen = "4k3/8/8/8/8/8/8/4KR1R w F - 0 1";
+        let game = Gamestate::from_str(fen).unwrap();
+        assert_eq!(game.castling, ('F', '-', '-', '-'));
+        assert_eq!(game.castling_rook(Color::White, true), Some((7, 5)));
+        assert_eq!(game.to_fen(), fen);
+        assert!(game.is_legal_position().is_ok());
+
+        let game = Gamestate::from_str("4k3/8/8/8/8/8/8/4KR1R w K - 0 1").unwrap();
+        assert_eq!(game.castling_rook(Color::White, true), Some((7, 7)));
+        assert_eq!(castling_letter(&game.board[7], 'R', 4, 5), 'F');
+        assert_eq!(castling_letter(&game.board[7], 'R', 4, 7), 'K');
+    }
+
+    #[test]
+    fn test_start_position_is_not_endgame() {
+        let game = Gamestate::from_str(START_FEN).unwrap();
+        assert!(!game.is_endgame());
+    }
+
+    #[test]
+    fn test_rook_endgame() {
+        let game = Gamestate::from_str("4k2r/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert!(game.is_endgame());
+    }
+
+    #[test]
+    fn test_can_castle_blocked_at_start() {
+        let game = Gamestate::from_str(START_FEN).unwrap();
+        for color in [Color::White, Color::Black] {
+            assert!(!game.can_castle(color, true));
+            assert!(!game.can_castle(color, false));
+        }
+    }
+
+    #[test]
+    fn test_can_castle_after_pieces_clear() {
+        // White has cleared the kingside, black has cleared the queenside
+        let game =
+            Gamestate::from_str("r3kbnr/pppqpppp/2n5/3p1b2/8/5NP1/PPPPPPBP/RNBQK2R w KQkq - 0 1")
+                .unwrap();
+        assert!(game.can_castle(Color::White, true));
+        assert!(!game.can_castle(Color::White, false));
+        assert!(!game.can_castle(Color::Black, true));
+        assert!(game.can_castle(Color::Black, false));
+
+        // Same squares, but the rights are gone
+        let game =
+            Gamestate::from_str("r3kbnr/pppqpppp/2n5/3p1b2/8/5NP1/PPPPPPBP/RNBQK2R w Qk - 0 1")
+                .unwrap();
+        assert!(!game.can_castle(Color::White, true));
+        assert!(!game.can_castle(Color::Black, false));
+    }
+
+    #[test]
+    fn test_en_passant_capturers() {
+        let game = Gamestate::from_str("8/8/8/K2pP3/8/8/8/7k w - d6 0 1").unwrap();
+        assert_eq!(game.en_passant_capturers(), vec![(3, 4)]);
+    }
+
+    #[test]
+    fn test_en_passant_capturer_pinned_on_rank() {
+        // exd6 would remove both pawns from the fifth rank and expose the king to the rook
+        let game = Gamestate::from_str("8/8/8/K2pP2r/8/8/8/7k w - d6 0 1").unwrap();
+        assert!(game.enpassat.is_some());
+        assert!(game.en_passant_capturers().is_empty());
+    }
+
+    #[test]
+    fn test_to_fen_strict() {
+        // 1. e4: no black pawn can take on e3
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        let game = Gamestate::from_str(fen).unwrap();
+        assert_eq!(game.to_fen_strict(), fen.replace(" e3 ", " - "));
+
+        let capturable = "8/8/8/K2pP3/8/8/8/7k w - d6 0 1";
+        assert_eq!(Gamestate::from_str(capturable).unwrap().to_fen_strict(), capturable);
+
+        // The e5 pawn could take on d6, but only by exposing its king to the rook
+        let pinned = "8/8/8/K2pP2r/8/8/8/7k w - d6 0 1";
+        let game = Gamestate::from_str(pinned).unwrap();
+        assert_eq!(game.to_fen(), pinned);
+        assert_eq!(game.to_fen_strict(), pinned.replace(" d6 ", " - "));
+    }
+
+    #[test]
+    fn test_to_fen_en_passant_needs_a_capturer() {
+        // 1. e4: no black pawn stands beside e4
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        assert_eq!(
+            Gamestate::from_str(fen).unwrap().to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+
+        // 1. e4 c5 2. e5 d5: the e5 pawn can take on d6
+        let fen = "rnbqkbnr/pp2pppp/8/2ppP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3";
+        assert_eq!(Gamestate::from_str(fen).unwrap().to_fen(), fen);
+
+        // The black pawn beside e4 is not the side to move, so it cannot capture
+        let fen = "4k3/8/8/8/3pP3/8/8/4K3 w - e3 0 1";
+        assert_eq!(Gamestate::from_str(fen).unwrap().to_fen(), "4k3/8/8/8/3pP3/8/8/4K3 w - - 0 1");
+    }
+
+    #[test]
+    fn test_to_fen_position_across_move_orders() {
+        let start = Gamestate::starting_position();
+        let (e4, e5) = (((6, 4), (4, 4), None), ((1, 4), (3, 4), None));
+        let (nf3, nc6) = (((7, 6), (5, 5), None), ((0, 1), (2, 2), None));
+
+        // 1. e4 e5 2. Nf3 Nc6 and 1. Nf3 Nc6 2. e4 e5
+        let first = start.with_moves(&[e4, e5, nf3, nc6]).unwrap();
+        let second = start.with_moves(&[nf3, nc6, e4, e5]).unwrap();
+        assert_ne!(first.to_fen(), second.to_fen());
+        assert_eq!(first.to_fen_position(), second.to_fen_position());
+        assert_eq!(
+            first.to_fen_position(),
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq -"
+        );
+    }
+}
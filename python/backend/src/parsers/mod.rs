@@ -6,3 +6,4 @@ pub mod parse_error;
 pub mod parse_input;
 pub mod parse_output;
 pub mod pgn_parser;
+pub mod san;
@@ -1,4 +1,112 @@
 /// Author: Renier Barnard
+use std::fmt;
+use std::str::FromStr;
+
+/// A single move between two board coordinates, with an optional promotion piece.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: (u8, u8),
+    pub to: (u8, u8),
+    /// Lowercase promotion piece letter (`q`, `r`, `b` or `n`), if the move promotes
+    pub promotion: Option<char>,
+}
+
+impl Move {
+    pub fn new(from: (u8, u8), to: (u8, u8)) -> Self {
+        Self {
+            from,
+            to,
+            promotion: None,
+        }
+    }
+
+    pub fn with_promotion(from: (u8, u8), to: (u8, u8), piece: char) -> Self {
+        Self {
+            from,
+            to,
+            promotion: Some(piece.to_ascii_lowercase()),
+        }
+    }
+}
+
+/// Formats the move in dash notation, e.g. `e2-e4` or `e7-e8q`. The promotion letter
+/// is always lowercase, so the result parses back with `from_str`.
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let from = index_to_chess_notation(self.from).ok_or(fmt::Error)?;
+        let to = index_to_chess_notation(self.to).ok_or(fmt::Error)?;
+        write!(f, "{}-{}", from, to)?;
+        if let Some(piece) = self.promotion {
+            write!(f, "{}", piece.to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses dash notation as written by `Display`: `e2-e4`, or `e7-e8q` with a trailing
+/// promotion letter
+impl FromStr for Move {
+    type Err = String;
+
+    fn from_str(notation: &str) -> Result<Self, Self::Err> {
+        let (from, to) = notation
+            .split_once('-')
+            .ok_or_else(|| format!("Invalid move notation: '{}'", notation))?;
+        let (to, promotion) = split_promotion(to, false)?;
+        build_move(notation, from, to, promotion)
+    }
+}
+
+/// Parses a move as clients send it in `moves`: dash notation when there is a `-`
+/// (see `Move::from_str`), otherwise UCI, so `e7-e8q` and `e7e8q` are the same move
+pub fn parse_move(notation: &str) -> Result<Move, String> {
+    if notation.contains('-') {
+        Move::from_str(notation)
+    } else {
+        parse_uci_move(notation)
+    }
+}
+
+/// Parses a UCI long-algebraic move: `e2e4`, or `e7e8q` / `a2a1n` with a trailing
+/// promotion letter
+pub fn parse_uci_move(notation: &str) -> Result<Move, String> {
+    if !notation.is_ascii() || notation.len() < 4 {
+        return Err(format!("Invalid UCI move: '{}'", notation));
+    }
+    let (from, rest) = notation.split_at(2);
+    let (to, promotion) = split_promotion(rest, true)?;
+    build_move(notation, from, to, promotion)
+}
+
+/// Splits a destination like `e8q` into the square and its promotion piece. `strict`
+/// (UCI) only accepts a trailing lowercase `q`, `r`, `b` or `n`; otherwise, as in the
+/// dash notation clients type, the piece may also follow `=` and be in either case, so
+/// `e8q`, `e8Q` and `e8=Q` are the same.
+fn split_promotion(to: &str, strict: bool) -> Result<(&str, Option<char>), String> {
+    let (square, piece) = match to.split_once('=') {
+        Some(split) if !strict => split,
+        _ if to.is_ascii() && to.len() > 2 => to.split_at(2),
+        _ => return Ok((to, None)),
+    };
+    let letter = if strict { piece.to_string() } else { piece.to_ascii_lowercase() };
+    match letter.as_str() {
+        letter @ ("q" | "r" | "b" | "n") => Ok((square, letter.chars().next())),
+        _ => Err(format!("Invalid promotion piece: '{}'", piece)),
+    }
+}
+
+fn build_move(
+    notation: &str,
+    from: &str,
+    to: &str,
+    promotion: Option<char>,
+) -> Result<Move, String> {
+    match (chess_notation_to_index(from), chess_notation_to_index(to)) {
+        (Some(from), Some(to)) => Ok(Move { from, to, promotion }),
+        _ => Err(format!("Invalid move notation: '{}'", notation)),
+    }
+}
+
 /// Converts a chess notation string (e.g. "e2") to an index pair (e.g. (1, 4)).
 ///
 /// The returned `(row, col)` is 0-indexed, with the following layout:
@@ -34,3 +142,61 @@ pub fn index_to_chess_notation(position: (u8, u8)) -> Option<String> {
         (b'0' + 8 - rank) as char
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::fen_parser::Gamestate;
+    use crate::validation::make_move::apply_move;
+
+    #[test]
+    fn test_promotion_in_both_notations() {
+        let uci = parse_uci_move("a7a8q").unwrap();
+        assert_eq!(uci, Move::with_promotion((1, 0), (0, 0), 'q'));
+        assert_eq!(Move::from_str("a7-a8q").unwrap(), uci);
+        assert_eq!(parse_uci_move("a2a1n").unwrap().promotion, Some('n'));
+        assert_eq!(Move::from_str(&uci.to_string()).unwrap(), uci);
+
+        let mut game = Gamestate::from_str("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        apply_move(&mut game, &uci);
+        assert_eq!(game.board[0][0], 'Q');
+        assert_eq!(game.board[1][0], ' ');
+    }
+
+    #[test]
+    fn test_rejects_malformed_moves() {
+        assert_eq!(parse_uci_move("e2e4").unwrap(), Move::new((6, 4), (4, 4)));
+        assert!(parse_uci_move("a7a8k").is_err());
+        assert!(parse_uci_move("a7a8qq").is_err());
+        assert!(parse_uci_move("a7a").is_err());
+        assert!(Move::from_str("a7-a8x").is_err());
+        assert!(Move::from_str("a7-a9").is_err());
+        assert!(Move::from_str("a7a8").is_err());
+        assert!(Move::from_str("a7-a8=").is_err());
+        assert!(Move::from_str("a7-a8=K").is_err());
+        assert!(Move::from_str("a7-a8=qq").is_err());
+    }
+
+    #[test]
+    fn test_promotion_suffix_forms() {
+        let knight = Move::with_promotion((1, 4), (0, 4), 'n');
+        for notation in ["e7-e8n", "e7-e8N", "e7-e8=N", "e7-e8=n"] {
+            assert_eq!(Move::from_str(notation).unwrap(), knight, "{}", notation);
+        }
+        assert_eq!(Move::from_str("e7-e8").unwrap().promotion, None);
+
+        // UCI keeps to the lowercase letter
+        assert!(parse_uci_move("e7e8N").is_err());
+        assert!(parse_uci_move("e7e8=n").is_err());
+    }
+
+    #[test]
+    fn test_parse_move_either_notation() {
+        let queen = Move::with_promotion((1, 4), (0, 4), 'q');
+        assert_eq!(parse_move("e7e8q").unwrap(), queen);
+        assert_eq!(parse_move("e7-e8=Q").unwrap(), queen);
+        assert_eq!(parse_move("g1f3").unwrap(), parse_move("g1-f3").unwrap());
+        assert!(parse_move("e7e8Q").is_err());
+        assert!(parse_move("e7-e8x").is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use super::fen_parser::Gamestate;
+use crate::validation::possible_moves::GameStatus;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::{error::Error, fmt};
@@ -7,6 +8,10 @@ use std::{error::Error, fmt};
 pub struct ParseError {
     error: String,
     fen: String,
+    /// Set when the request was refused because of how the game stands, e.g. a move
+    /// sent after checkmate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<GameStatus>,
 }
 
 impl ParseError {
@@ -14,14 +19,25 @@ impl ParseError {
         Self {
             error: error.to_string(),
             fen: Gamestate::to_fen(game_state),
+            status: None,
         }
     }
 
+    /// Tags the error with the game status that caused it
+    pub fn with_status(mut self, status: GameStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
     pub fn to_json(&self) -> Value {
-        json!({
+        let mut json = json!({
             "error": self.error,
             "fen": self.fen
-        })
+        });
+        if let Some(status) = self.status {
+            json["status"] = json!(status);
+        }
+        json
     }
 
     pub fn print_stderr(&self) {
@@ -1,13 +1,37 @@
 use super::fen_parser::Gamestate;
+use crate::validation::pieces::Color;
 use serde::{Deserialize, Serialize};
-use serde_json::from_str;
+use serde_json::{from_str, Value};
 use std::{io, str::FromStr};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct JsonInput {
     pub reason: String,
+    #[serde(default)]
     pub fen: String,
+    /// Structured alternative to `fen`, in the form of `Gamestate::to_json`
+    #[serde(default)]
+    pub state: Option<Value>,
+    #[serde(default)]
     pub moves: String,
+    /// The piece chosen to finish an interactive promotion
+    #[serde(default)]
+    pub piece: String,
+    /// The piece a promoting `move` becomes when its notation names none: Q, R, B or N
+    /// in either case
+    #[serde(default)]
+    pub promotion: Option<String>,
+    /// A board in the form of `Gamestate::board_string`, replacing the one in the FEN
+    #[serde(default)]
+    pub board: Option<String>,
+    /// Asks for the board string in the reply, for board editors
+    #[serde(default)]
+    pub include_board: bool,
+    /// `"white"` or `"black"`: the side the reply's board string is drawn from. Only
+    /// `board` is oriented; moves and squares elsewhere in the reply stay algebraic, as
+    /// clients send them back in later requests.
+    #[serde(default)]
+    pub orientation: Option<String>,
 }
 
 #[derive(Debug)]
@@ -15,29 +39,130 @@ pub struct JsonIn {
     pub reason: String,
     pub state: Gamestate,
     pub moves: String,
+    pub piece: String,
+    /// The lowercase promotion piece asked for alongside a `move`
+    pub promotion: Option<char>,
+    /// Set when the board string was asked for, directly or by giving an orientation
+    pub include_board: bool,
+    pub orientation: Color,
 }
 
+/// Reasons that act on the position sent, so its FEN must be valid
+const POSITION_REASONS: [&str; 12] = [
+    "move", "validate", "status", "count", "mobility", "matein1", "bestmove", "trymove",
+    "movemap", "quiet", "opening", "see",
+];
+
 pub fn read_and_parse_input() -> Result<JsonIn, Box<dyn std::error::Error>> {
     let mut stdin: String = String::new();
     io::stdin().read_line(&mut stdin)?;
 
-    let input: JsonInput = from_str(&stdin).map_err(|e| Box::<dyn std::error::Error>::from(e))?;
+    parse_input(&stdin)
+}
+
+/// Parses a single line of the JSON game protocol
+pub fn parse_input(line: &str) -> Result<JsonIn, Box<dyn std::error::Error>> {
+    let input: JsonInput = from_str(line).map_err(Box::<dyn std::error::Error>::from)?;
 
-    // Parse the FEN string into a Gamestate
-    let state: Gamestate = if input.reason == "move" || input.reason == "validate" {
+    // Parse the structured state if given, otherwise the FEN string, into a Gamestate
+    let mut state: Gamestate = if let Some(state) = &input.state {
+        Gamestate::from_json(state)?
+    } else if POSITION_REASONS.contains(&input.reason.as_str()) {
         Gamestate::from_str(&input.fen)?
     } else {
         Gamestate::from_str(&input.fen).unwrap_or_else(|_| Gamestate::new())
     };
-    let moves: String = if input.reason == "move" {
+    if let Some(board) = &input.board {
+        state.set_board_string(board)?;
+    }
+    let orientation = match input.orientation.as_deref() {
+        None | Some("white") => Color::White,
+        Some("black") => Color::Black,
+        Some(other) => return Err(format!("Invalid orientation: '{}'", other).into()),
+    };
+    let moves: String = if ["move", "trymove", "see"].contains(&input.reason.as_str()) {
         input.moves
     } else {
         String::new()
     };
+    let promotion = match input.promotion.as_deref().map(|p| p.to_ascii_lowercase()) {
+        None => None,
+        Some(p) if ["q", "r", "b", "n"].contains(&p.as_str()) => p.chars().next(),
+        Some(_) => {
+            let piece = input.promotion.unwrap_or_default();
+            return Err(format!("Invalid promotion piece: '{}'", piece).into());
+        }
+    };
+    let promotion = promotion.filter(|_| input.reason == "move");
+    let piece: String = if input.reason == "promote" {
+        input.piece
+    } else {
+        String::new()
+    };
 
     Ok(JsonIn {
         reason: input.reason,
         state,
         moves,
+        piece,
+        promotion,
+        include_board: input.include_board || input.orientation.is_some(),
+        orientation,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_structured_state() {
+        let state = Gamestate::from_str("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        let line = serde_json::json!({
+            "reason": "move",
+            "state": state.to_json(),
+            "moves": "e2-e4"
+        });
+        let input = parse_input(&line.to_string()).unwrap();
+        assert_eq!(input.state.to_fen(), state.to_fen());
+        assert_eq!(input.moves, "e2-e4");
+
+        let line = r#"{"reason": "move", "state": {"player": "w"}, "moves": "e2-e4"}"#;
+        assert!(parse_input(line).is_err());
+    }
+
+    #[test]
+    fn test_raw_board() {
+        let board = Gamestate::from_str("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap().board_string();
+        let line = serde_json::json!({
+            "reason": "status",
+            "fen": "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "board": board,
+            "include_board": true
+        });
+        let input = parse_input(&line.to_string()).unwrap();
+        assert_eq!(input.state.board_string(), board);
+        assert!(input.include_board);
+    }
+
+    #[test]
+    fn test_promotion_field() {
+        let fen = "4k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        let line = |promotion: &str| {
+            serde_json::json!({
+                "reason": "move",
+                "fen": fen,
+                "moves": "a7-a8",
+                "promotion": promotion
+            })
+            .to_string()
+        };
+        assert_eq!(parse_input(&line("N")).unwrap().promotion, Some('n'));
+        assert_eq!(parse_input(&line("r")).unwrap().promotion, Some('r'));
+        assert!(parse_input(&line("K")).is_err());
+        assert!(parse_input(&line("queen")).is_err());
+
+        let line = format!(r#"{{"reason":"move","fen":"{}","moves":"a7-a8"}}"#, fen);
+        assert_eq!(parse_input(&line).unwrap().promotion, None);
+    }
+}
@@ -1,17 +1,78 @@
 use super::fen_parser::Gamestate;
+use super::notation::index_to_chess_notation;
+use crate::validation::evaluation::material_of;
+use crate::validation::pieces::Color;
+use crate::validation::possible_moves::{is_threefold_count, GameStatus};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::fmt;
 
+/// Material on the board per side, in centipawns
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Material {
+    pub white: i32,
+    pub black: i32,
+    /// `white - black`: positive when White leads
+    pub diff: i32,
+}
+
+impl Material {
+    pub fn of(board: &[[char; 8]; 8]) -> Self {
+        let white = material_of(board, Color::White);
+        let black = material_of(board, Color::Black);
+        Self {
+            white,
+            black,
+            diff: white - black,
+        }
+    }
+}
+
+/// Turns a `Gamestate::board_string` (a8 first, h1 last) to be drawn top to bottom
+/// from `orientation`'s side. From Black's side both ranks and files are reversed, so
+/// the string runs from h1 to a8. Nothing else in a reply is oriented: `possible_moves`
+/// keeps its algebraic squares.
+pub fn orient_board(board: &str, orientation: Color) -> String {
+    match orientation {
+        Color::White => board.to_string(),
+        Color::Black => board.chars().rev().collect(),
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ParseOut {
+    /// Human-readable status, for display
     message: String,
+    /// The status as an enum clients can match on
+    status: GameStatus,
     fen: String,
+    /// The en passant target square written in `fen`, e.g. `"e6"`: only set while a
+    /// pawn stands ready to take there (see `Gamestate::en_passant_square`)
+    en_passant: Option<String>,
     possible_moves: Vec<String>,
     in_check: bool,
     checkmate: bool,
     stalemate: bool,
     game_over: bool,
+    /// Halfmoves since the last capture or pawn move
+    halfmove_clock: u8,
+    /// Full moves left before the 50-move rule draw applies
+    moves_until_draw: u8,
+    /// How many times the current position has occurred in the session (1-3+)
+    repetition_count: u8,
+    /// The position has occurred three times, so either player may claim a draw
+    draw_claimable: bool,
+    /// Neither side can force mate with the material left (see `validation::draw`)
+    practical_draw: bool,
+    /// Why the game was drawn, when it has been
+    draw_reason: Option<String>,
+    /// `"white"` or `"black"` once the game has been won
+    winner: Option<String>,
+    /// Only reported when the CLI runs with `--material`
+    material: Option<Material>,
+    /// The board as `Gamestate::board_string`, when the request asked for it, turned by
+    /// `orient_board` to the requested side
+    board: Option<String>,
 }
 
 impl ParseOut {
@@ -24,26 +85,92 @@ impl ParseOut {
         stalemate: bool,
     ) -> Self {
         Self {
-            message: String::from(message),
+            message,
+            status: GameStatus::Ongoing,
             fen: Gamestate::to_fen(game_state),
+            en_passant: game_state.en_passant_square().and_then(index_to_chess_notation),
             possible_moves,
             in_check,
             checkmate,
             stalemate,
             game_over: checkmate || stalemate,
+            halfmove_clock: game_state.halfmove,
+            moves_until_draw: 100u8.saturating_sub(game_state.halfmove) / 2,
+            repetition_count: 1,
+            draw_claimable: false,
+            practical_draw: false,
+            draw_reason: None,
+            winner: None,
+            material: None,
+            board: None,
         }
     }
 
+    /// Sets how often the current position has been seen, from the session history.
+    /// A third occurrence makes the draw claimable.
+    pub fn with_repetition_count(mut self, repetition_count: u8) -> Self {
+        self.repetition_count = repetition_count;
+        self.draw_claimable = is_threefold_count(repetition_count);
+        self
+    }
+
+    /// Sets the structured game status reported next to `message`
+    pub fn with_status(mut self, status: GameStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Flags a practical draw: mate is still possible, but only with the loser's help
+    pub fn with_practical_draw(mut self, practical_draw: bool) -> Self {
+        self.practical_draw = practical_draw;
+        self
+    }
+
+    /// Records how a finished game ended: the draw reason or the winner
+    pub fn with_result(mut self, draw_reason: Option<&str>, winner: Option<&str>) -> Self {
+        self.draw_reason = draw_reason.map(str::to_string);
+        self.winner = winner.map(str::to_string);
+        self
+    }
+
+    /// Adds the per-side material to the output
+    pub fn with_material(mut self, material: Option<Material>) -> Self {
+        self.material = material;
+        self
+    }
+
+    /// Adds the raw board string to the output
+    pub fn with_board(mut self, board: Option<String>) -> Self {
+        self.board = board;
+        self
+    }
+
     pub fn to_json(&self) -> Value {
-        json!({
+        let mut json = json!({
             "message": self.message,
+            "status": self.status,
             "fen": self.fen,
+            "en_passant": self.en_passant,
             "possible_moves": self.possible_moves,
             "in_check": self.in_check,
             "checkmate": self.checkmate,
             "stalemate": self.stalemate,
-            "game_over": self.game_over
-        })
+            "game_over": self.game_over,
+            "halfmove_clock": self.halfmove_clock,
+            "moves_until_draw": self.moves_until_draw,
+            "repetition_count": self.repetition_count,
+            "draw_claimable": self.draw_claimable,
+            "practical_draw": self.practical_draw,
+            "draw_reason": self.draw_reason,
+            "winner": self.winner
+        });
+        if let Some(material) = self.material {
+            json["material"] = json!(material);
+        }
+        if let Some(board) = &self.board {
+            json["board"] = json!(board);
+        }
+        json
     }
 
     pub fn print_stdout(&self) {
@@ -56,3 +183,67 @@ impl fmt::Display for ParseOut {
         write!(f, "ParseOut: {}", self.message)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::notation::Move;
+    use crate::validation::make_move::apply_move;
+    use std::str::FromStr;
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    fn output_for(fen: &str) -> Value {
+        let game = Gamestate::from_str(fen).unwrap();
+        ParseOut::new("valid".to_string(), &game, Vec::new(), false, false, false).to_json()
+    }
+
+    #[test]
+    fn test_material() {
+        // White is up a knight
+        let game = Gamestate::from_str("4k3/pppp4/8/8/8/8/PPPP4/4KN2 w - - 0 1").unwrap();
+        let json = ParseOut::new("valid".to_string(), &game, Vec::new(), false, false, false)
+            .with_material(Some(Material::of(&game.board)))
+            .to_json();
+        assert_eq!(json["material"], json!({ "white": 720, "black": 400, "diff": 320 }));
+
+        assert!(output_for(START_FEN).get("material").is_none());
+    }
+
+    #[test]
+    fn test_en_passant_square() {
+        assert_eq!(output_for(START_FEN)["en_passant"], Value::Null);
+
+        let mut game = Gamestate::from_str(START_FEN).unwrap();
+        let output = |game: &Gamestate| {
+            ParseOut::new("valid".to_string(), game, Vec::new(), false, false, false).to_json()
+        };
+        apply_move(&mut game, &Move::new((6, 4), (4, 4))); // e2-e4
+        apply_move(&mut game, &Move::new((1, 4), (3, 4))); // e7-e5
+        // No white pawn beside e5, so e6 is left out like in the FEN
+        assert_eq!(output(&game)["en_passant"], Value::Null);
+
+        let mut game = Gamestate::from_str(START_FEN).unwrap();
+        apply_move(&mut game, &Move::new((6, 4), (4, 4))); // e2-e4
+        apply_move(&mut game, &Move::new((1, 0), (2, 0))); // a7-a6
+        apply_move(&mut game, &Move::new((4, 4), (3, 4))); // e4-e5
+        apply_move(&mut game, &Move::new((1, 3), (3, 3))); // d7-d5, beside the e5 pawn
+        let json = output(&game);
+        assert_eq!(json["en_passant"], "d6");
+        assert!(json["fen"].as_str().unwrap().contains(" d6 "));
+    }
+
+    #[test]
+    fn test_fresh_game_fifty_move_counter() {
+        let json = output_for(START_FEN);
+        assert_eq!(json["halfmove_clock"], 0);
+        assert_eq!(json["moves_until_draw"], 50);
+    }
+
+    #[test]
+    fn test_fifty_move_counter_mid_game() {
+        let json = output_for("4k3/8/8/8/8/8/8/4K3 w - - 37 60");
+        assert_eq!(json["halfmove_clock"], 37);
+        assert_eq!(json["moves_until_draw"], 31);
+    }
+}
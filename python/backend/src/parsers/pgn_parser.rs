@@ -7,8 +7,10 @@
  * - Fixed borrow-after-move error in from_pgn (lines 64-68)
  */
 
+use crate::parsers::fen_parser::Gamestate;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::str::FromStr;
 
 /// Represents a complete PGN (Portable Game Notation) chess game
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -17,6 +19,13 @@ pub struct PgnGame {
     pub headers: HashMap<String, String>,
     /// List of moves in standard algebraic notation
     pub moves: Vec<String>,
+    /// The `{...}` comment following each move, aligned with `moves` by index
+    #[serde(default)]
+    pub comments: Vec<Option<String>>,
+    /// Numeric annotation glyphs for each move (`$1` good move, `$2` mistake, ...),
+    /// aligned with `moves` by index. Suffixes such as `!?` are stored as their NAG.
+    #[serde(default)]
+    pub nags: Vec<Vec<u8>>,
     /// Game result: "1-0" (White wins), "0-1" (Black wins), "1/2-1/2" (Draw), "*" (Ongoing)
     pub result: String,
 }
@@ -27,14 +36,40 @@ impl PgnGame {
         Self {
             headers: HashMap::new(),
             moves: Vec::new(),
+            comments: Vec::new(),
+            nags: Vec::new(),
             result: "*".to_string(),
         }
     }
 
+    /// Creates an empty game that starts from `game`.
+    ///
+    /// Positions other than the standard start are recorded in `SetUp "1"` and
+    /// `FEN` headers, which `starting_position` reads back.
+    pub fn from_position(game: &Gamestate) -> Self {
+        let mut pgn = PgnGame::new();
+        let fen = game.to_fen();
+        if fen != Gamestate::starting_position().to_fen() {
+            pgn.set_header("SetUp".to_string(), "1".to_string());
+            pgn.set_header("FEN".to_string(), fen);
+        }
+        pgn
+    }
+
+    /// Returns the position the game starts from: the `FEN` header when `SetUp` is
+    /// `"1"`, otherwise the standard starting position
+    pub fn starting_position(&self) -> Result<Gamestate, String> {
+        match (self.get_header("SetUp"), self.get_header("FEN")) {
+            (Some(setup), Some(fen)) if setup == "1" => Gamestate::from_str(fen)
+                .map_err(|e| format!("invalid FEN header '{}': {}", fen, e)),
+            _ => Ok(Gamestate::starting_position()),
+        }
+    }
+
     /// Parses a PGN string into a PgnGame struct
     ///
     /// # Example PGN Format:
-    /// ```
+    /// ```text
     /// [Event "World Championship"]
     /// [Site "New York"]
     /// [Date "1972.07.11"]
@@ -49,20 +84,24 @@ impl PgnGame {
         let mut game = PgnGame::new();
         let mut in_headers = true;
         let mut move_text = String::new();
+        let mut in_brace_comment = false;
+
+        // Treat CRLF (Windows) and bare CR line endings like '\n'
+        let normalized = pgn_str.replace("\r\n", "\n").replace('\r', "\n");
 
-        for line in pgn_str.lines() {
+        for line in normalized.split('\n') {
             let line = line.trim();
 
-            // Skip empty lines
+            // Every input line ends in a '\n' in the move text, headers and blank lines
+            // included, so tokens can be traced back to their line number
             if line.is_empty() {
-                if in_headers {
-                    in_headers = false; // Headers section ended
-                }
+                move_text.push('\n');
                 continue;
             }
 
-            // Parse header tags [Key "Value"]
-            if line.starts_with('[') && line.ends_with(']') {
+            // Parse header tags [Key "Value"]. The first line that isn't a tag ends the
+            // header section, even without a blank line before the moves.
+            if in_headers && line.starts_with('[') && line.contains(']') {
                 if let Some((key, value)) = parse_header_line(line) {
                     // FIX: Check key BEFORE moving values into insert
                     if key == "Result" {
@@ -72,13 +111,17 @@ impl PgnGame {
                 }
             } else {
                 // This is move text
-                move_text.push_str(line);
-                move_text.push(' ');
+                in_headers = false;
+                move_text.push_str(strip_line_comment(line, &mut in_brace_comment));
             }
+            move_text.push('\n');
         }
 
         // Parse the moves from the accumulated move text
-        game.moves = parse_move_text(&move_text)?;
+        let parsed = parse_move_text(&move_text)?;
+        game.moves = parsed.moves;
+        game.comments = parsed.comments;
+        game.nags = parsed.nags;
 
         // If no Result tag was found, try to extract from move text
         if game.result == "*" {
@@ -125,11 +168,21 @@ impl PgnGame {
             let move_num = (i / 2) + 1;
             let is_white = i % 2 == 0;
 
-            let move_str = if is_white {
+            // A black move that follows a comment repeats its number as "N..."
+            let after_comment = i > 0 && matches!(self.comments.get(i - 1), Some(Some(_)));
+            let mut move_str = if is_white {
                 format!("{}. {} ", move_num, move_san)
+            } else if after_comment {
+                format!("{}... {} ", move_num, move_san)
             } else {
                 format!("{} ", move_san)
             };
+            for nag in self.nags.get(i).into_iter().flatten() {
+                move_str.push_str(&format!("${} ", nag));
+            }
+            if let Some(Some(comment)) = self.comments.get(i) {
+                move_str.push_str(&format!("{{{}}} ", comment));
+            }
 
             if line.len() + move_str.len() > 80 {
                 pgn.push_str(&line);
@@ -153,6 +206,8 @@ impl PgnGame {
     /// Adds a move to the game in standard algebraic notation (SAN)
     pub fn add_move(&mut self, san_move: String) {
         self.moves.push(san_move);
+        self.comments.push(None);
+        self.nags.push(Vec::new());
     }
 
     /// Sets a header value
@@ -166,13 +221,55 @@ impl PgnGame {
     }
 }
 
+impl Default for PgnGame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Headers shown in a game summary, in display order
+const SUMMARY_HEADERS: [&str; 5] = ["Event", "Site", "Date", "White", "Black"];
+
+/// The parts of a parsed PGN game that get reported back: the main headers, the moves,
+/// the result and the game written out again as PGN
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PgnSummary {
+    /// `(tag, value)` for each of Event, Site, Date, White and Black that is present
+    pub headers: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    pub result: String,
+    pub pgn_text: String,
+}
+
+impl PgnSummary {
+    pub fn new(game: &PgnGame) -> Self {
+        let headers = SUMMARY_HEADERS
+            .iter()
+            .filter_map(|&tag| Some((tag.to_string(), game.get_header(tag)?.clone())))
+            .collect();
+        Self {
+            headers,
+            moves: game.moves.clone(),
+            result: game.result.clone(),
+            pgn_text: game.to_pgn(),
+        }
+    }
+}
+
+/// Parses a PGN string and summarises the game
+pub fn process_pgn(pgn_input: &str) -> Result<PgnSummary, String> {
+    Ok(PgnSummary::new(&PgnGame::from_pgn(pgn_input)?))
+}
+
 /// Parses a header line like [Event "World Championship"]
 fn parse_header_line(line: &str) -> Option<(String, String)> {
-    // Remove brackets
-    let content = line.trim_matches(|c| c == '[' || c == ']').trim();
+    // Remove brackets, ignoring anything (e.g. stray whitespace) after the closing one
+    let line = line.trim();
+    let content = line.strip_prefix('[')?;
+    let content = content[..content.rfind(']')?].trim();
 
-    // Find the first space (separates key from value)
-    if let Some(space_pos) = content.find(' ') {
+    // Find the first space or tab (separates key from value)
+    if let Some(space_pos) = content.find(char::is_whitespace) {
         let key = content[..space_pos].trim().to_string();
         let value_part = content[space_pos + 1..].trim();
 
@@ -185,12 +282,111 @@ fn parse_header_line(line: &str) -> Option<(String, String)> {
     }
 }
 
-/// Parses move text and extracts moves in standard algebraic notation
-fn parse_move_text(text: &str) -> Result<Vec<String>, String> {
-    let mut moves = Vec::new();
-    let tokens: Vec<&str> = text.split_whitespace().collect();
+/// Cuts a `;` comment (which runs to the end of the line) off a line of move text.
+///
+/// A `;` inside a `{...}` comment is just text; `in_brace_comment` carries whether a
+/// brace comment is still open from an earlier line.
+fn strip_line_comment<'a>(line: &'a str, in_brace_comment: &mut bool) -> &'a str {
+    for (i, c) in line.char_indices() {
+        match c {
+            '{' => *in_brace_comment = true,
+            '}' => *in_brace_comment = false,
+            ';' if !*in_brace_comment => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+/// Splits move text into whitespace-separated tokens, keeping each `{...}` comment
+/// (which may contain spaces or line breaks) together as a single token.
+///
+/// Each token comes with the 1-based line it starts on, counting the `'\n'`s before it.
+fn tokenize_move_text(text: &str) -> Vec<(usize, String)> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_comment = false;
+    let mut line = 1;
+    let mut token_line = 1;
+
+    for c in text.chars() {
+        if in_comment {
+            current.push(c);
+            if c == '}' {
+                tokens.push((token_line, std::mem::take(&mut current)));
+                in_comment = false;
+            }
+        } else if c == '{' {
+            if !current.is_empty() {
+                tokens.push((token_line, std::mem::take(&mut current)));
+            }
+            current.push(c);
+            token_line = line;
+            in_comment = true;
+        } else if c.is_whitespace() {
+            if !current.is_empty() {
+                tokens.push((token_line, std::mem::take(&mut current)));
+            }
+        } else {
+            if current.is_empty() {
+                token_line = line;
+            }
+            current.push(c);
+        }
+
+        if c == '\n' {
+            line += 1;
+        }
+    }
+    // An unterminated comment runs to the end of the text
+    if !current.is_empty() {
+        tokens.push((token_line, current));
+    }
+
+    tokens
+}
 
-    for token in tokens.iter() {
+/// Moves parsed from move text, with the annotations attached to each
+#[derive(Debug, Default)]
+struct MoveText {
+    moves: Vec<String>,
+    comments: Vec<Option<String>>,
+    nags: Vec<Vec<u8>>,
+}
+
+impl MoveText {
+    fn push_move(&mut self, san: &str, nags: Vec<u8>) {
+        self.moves.push(san.to_string());
+        self.comments.push(None);
+        self.nags.push(nags);
+    }
+}
+
+/// Maps a move suffix annotation to its NAG (`!` is `$1`, `?` is `$2`, ...)
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+/// Parses move text and extracts moves in standard algebraic notation.
+///
+/// Comments and NAGs are attached to the move before them. Those that come before
+/// the first move have nothing to attach to and are dropped.
+///
+/// A token that is not a move, move number, result or annotation is an error naming
+/// the token and its line, e.g. `invalid move 'Nf9' at line 12`.
+fn parse_move_text(text: &str) -> Result<MoveText, String> {
+    let mut parsed = MoveText::default();
+    let tokens: Vec<(usize, String)> = tokenize_move_text(text);
+
+    for (line, token) in tokens.iter() {
         let token = token.trim();
 
         // Skip empty tokens
@@ -198,31 +394,65 @@ fn parse_move_text(text: &str) -> Result<Vec<String>, String> {
             continue;
         }
 
-        // Skip move numbers (e.g., "1.", "2.", "10.")
-        if token.ends_with('.') && token[..token.len() - 1].parse::<u32>().is_ok() {
+        // Skip move numbers (e.g., "1.", "10.", or "1..." for black), which may be glued to the move
+        let token = strip_move_number(token);
+        if token.is_empty() {
             continue;
         }
 
-        // Skip result indicators
-        if matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+        // Skip result indicators, including one glued to the last move (e.g. "Qxf7#1-0")
+        let (token, _) = split_trailing_result(token);
+        if token.is_empty() {
             continue;
         }
 
-        // Skip comments (simplified - just skip text in {})
-        if token.starts_with('{') {
+        // Comments arrive as a single `{...}` token
+        if let Some(comment) = token.strip_prefix('{') {
+            let comment = comment.strip_suffix('}').unwrap_or(comment).trim();
+            if let Some(last) = parsed.comments.last_mut() {
+                *last = Some(match last.take() {
+                    Some(previous) => format!("{} {}", previous, comment),
+                    None => comment.to_string(),
+                });
+            }
             continue;
         }
 
-        // Skip annotations like !!, !?, ??, etc.
+        // Numeric annotation glyphs like $1 or $14
+        if let Some(number) = token.strip_prefix('$') {
+            if let (Ok(nag), Some(last)) = (number.parse::<u8>(), parsed.nags.last_mut()) {
+                last.push(nag);
+            }
+            continue;
+        }
+
+        // Annotations like !!, !?, ??, etc. become the move's NAG
         let clean_token = token.trim_end_matches(|c| "!?".contains(c));
+        let nags: Vec<u8> = suffix_nag(&token[clean_token.len()..]).into_iter().collect();
 
         // This should be a move
-        if !clean_token.is_empty() && is_valid_san_move(clean_token) {
-            moves.push(clean_token.to_string());
+        if clean_token.is_empty() {
+            continue;
+        }
+        if !is_valid_san_move(clean_token) {
+            return Err(format!("invalid move '{}' at line {}", token, line));
         }
+        parsed.push_move(clean_token, nags);
     }
 
-    Ok(moves)
+    Ok(parsed)
+}
+
+/// Strips a leading move number such as `1.`, `10.` or the black continuation `1...`
+/// from a token, returning what follows it (empty when the number stood alone)
+fn strip_move_number(token: &str) -> &str {
+    let rest = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.len() == token.len() {
+        return token;
+    }
+    rest.strip_prefix("...")
+        .or_else(|| rest.strip_prefix('.'))
+        .unwrap_or(token)
 }
 
 /// Basic validation for Standard Algebraic Notation moves
@@ -252,15 +482,31 @@ fn is_valid_san_move(san: &str) -> bool {
 
 /// Extracts game result from move text
 fn extract_result_from_moves(text: &str) -> Option<String> {
-    let tokens: Vec<&str> = text.split_whitespace().collect();
-    for token in tokens.iter().rev() {
-        if matches!(*token, "1-0" | "0-1" | "1/2-1/2" | "*") {
-            return Some(token.to_string());
+    let tokens: Vec<(usize, String)> = tokenize_move_text(text);
+    for (_, token) in tokens.iter().rev() {
+        if token.starts_with('{') {
+            continue;
+        }
+        if let (_, Some(result)) = split_trailing_result(token) {
+            return Some(result.to_string());
         }
     }
     None
 }
 
+/// Splits a game result (`1-0`, `0-1`, `1/2-1/2` or `*`) off the end of a token.
+///
+/// Returns the rest of the token and the result, if there was one. A token that is
+/// only a result comes back as `("", Some(result))`.
+fn split_trailing_result(token: &str) -> (&str, Option<&str>) {
+    for result in ["1/2-1/2", "1-0", "0-1", "*"] {
+        if let Some(rest) = token.strip_suffix(result) {
+            return (rest, Some(result));
+        }
+    }
+    (token, None)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -283,7 +529,27 @@ mod tests {
         assert_eq!(game.get_header("Event"), Some(&"Test Game".to_string()));
         assert_eq!(game.get_header("White"), Some(&"Player1".to_string()));
         assert_eq!(game.result, "1-0");
-        assert!(game.moves.len() > 0);
+        assert!(!game.moves.is_empty());
+    }
+
+    #[test]
+    fn test_process_pgn_summary() {
+        let summary = process_pgn(
+            "[Event \"Test Game\"]\n[White \"Player1\"]\n[Result \"1-0\"]\n\n\
+             1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 1-0\n",
+        )
+        .unwrap();
+        assert_eq!(summary.moves.len(), 6);
+        assert_eq!(summary.result, "1-0");
+        assert_eq!(
+            summary.headers,
+            vec![
+                ("Event".to_string(), "Test Game".to_string()),
+                ("White".to_string(), "Player1".to_string()),
+            ]
+        );
+        assert!(summary.pgn_text.contains("3. Bb5 a6 1-0"));
+        assert!(process_pgn("1. e4 e5 2. Qxz9").is_err());
     }
 
     #[test]
@@ -301,4 +567,117 @@ mod tests {
         assert!(pgn.contains("[Event \"Test\"]"));
         assert!(pgn.contains("1. e4 e5"));
     }
+
+    #[test]
+    fn test_leading_comment() {
+        let moves = parse_move_text("{ Annotator: Someone } 1. e4 e5").unwrap().moves;
+        assert_eq!(moves, vec!["e4", "e5"]);
+    }
+
+    #[test]
+    fn test_semicolon_comment() {
+        let game = PgnGame::from_pgn("1. e4 ; this is a comment").unwrap();
+        assert_eq!(game.moves, vec!["e4"]);
+
+        // The comment ends with its line, and a ';' inside braces is not a comment
+        let game = PgnGame::from_pgn("1. e4 ; Nf3 would transpose\ne5 {a; b} 2. Nf3").unwrap();
+        assert_eq!(game.moves, vec!["e4", "e5", "Nf3"]);
+    }
+
+    #[test]
+    fn test_black_continuation_numbers() {
+        let moves = parse_move_text("1. e4 { comment } 1... e5").unwrap().moves;
+        assert_eq!(moves, vec!["e4", "e5"]);
+
+        let moves = parse_move_text("12...Nf6 13.Bg5 1-0").unwrap().moves;
+        assert_eq!(moves, vec!["Nf6", "Bg5"]);
+    }
+
+    #[test]
+    fn test_result_glued_to_last_move() {
+        let game = PgnGame::from_pgn("1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7#1-0").unwrap();
+        assert_eq!(game.moves.last().map(String::as_str), Some("Qxf7#"));
+        assert_eq!(game.moves.len(), 7);
+        assert_eq!(game.result, "1-0");
+
+        let game = PgnGame::from_pgn("1. d4 d5 1/2-1/2").unwrap();
+        assert_eq!(game.result, "1/2-1/2");
+    }
+
+    #[test]
+    fn test_moves_directly_after_headers() {
+        let pgn_str = "[Event \"Casual\"]\n[White \"A\"]\n[Black \"B\"]\n1. e4 e5\n2. Nf3 *";
+        let game = PgnGame::from_pgn(pgn_str).unwrap();
+        assert_eq!(game.get_header("Black"), Some(&"B".to_string()));
+        assert_eq!(game.moves, vec!["e4", "e5", "Nf3"]);
+        assert_eq!(game.result, "*");
+    }
+
+    #[test]
+    fn test_crlf_line_endings() {
+        let pgn_str = "[Event \"Windows\"]\r\n[Site \"Here\"] \t\r\n[Result \"0-1\"]\r\n\r\n1. f3 e5\r\n2. g4 Qh4# 0-1\r\n";
+        let game = PgnGame::from_pgn(pgn_str).unwrap();
+        assert_eq!(game.get_header("Event"), Some(&"Windows".to_string()));
+        assert_eq!(game.get_header("Site"), Some(&"Here".to_string()));
+        assert_eq!(game.moves, vec!["f3", "e5", "g4", "Qh4#"]);
+        assert_eq!(game.result, "0-1");
+    }
+
+    #[test]
+    fn test_comment_with_move_like_words() {
+        // Words inside a comment must not be picked up as moves
+        let moves = parse_move_text("1. e4 {best by test, not d4} e5 2. Nf3").unwrap().moves;
+        assert_eq!(moves, vec!["e4", "e5", "Nf3"]);
+    }
+
+    #[test]
+    fn test_comment_round_trip() {
+        let game = PgnGame::from_pgn("1. e4 {good} e5").unwrap();
+        assert_eq!(game.moves, vec!["e4", "e5"]);
+        assert_eq!(game.comments, vec![Some("good".to_string()), None]);
+
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("1. e4 {good} 1... e5"));
+        let reparsed = PgnGame::from_pgn(&pgn).unwrap();
+        assert_eq!(reparsed.moves, game.moves);
+        assert_eq!(reparsed.comments, game.comments);
+    }
+
+    #[test]
+    fn test_nags() {
+        let game = PgnGame::from_pgn("1. e4! $14 e5?! 2. Nf3").unwrap();
+        assert_eq!(game.moves, vec!["e4", "e5", "Nf3"]);
+        assert_eq!(game.nags, vec![vec![1, 14], vec![6], vec![]]);
+        assert!(game.to_pgn().contains("1. e4 $1 $14 e5 $6 2. Nf3"));
+    }
+
+    #[test]
+    fn test_invalid_move_reports_token_and_line() {
+        let pgn_str = "[Event \"Typo\"]\n\n1. e4 e5\n2. Nf9 Nc6 *";
+        let err = PgnGame::from_pgn(pgn_str).unwrap_err();
+        assert_eq!(err, "invalid move 'Nf9' at line 4");
+
+        // Lines inside a multi-line comment still count
+        let err = parse_move_text("1. e4 {a long\ncomment} e5\nQx").unwrap_err();
+        assert!(err.contains("'Qx'") && err.contains("line 3"), "{}", err);
+    }
+
+    #[test]
+    fn test_from_position() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let game = PgnGame::from_position(&Gamestate::from_str(fen).unwrap());
+        assert_eq!(game.get_header("SetUp"), Some(&"1".to_string()));
+        assert_eq!(game.get_header("FEN"), Some(&fen.to_string()));
+        assert!(game.moves.is_empty());
+        assert!(game.to_pgn().contains(&format!("[FEN \"{}\"]", fen)));
+        assert_eq!(game.starting_position().unwrap().to_fen(), fen);
+
+        // The standard start needs no setup tags
+        let game = PgnGame::from_position(&Gamestate::starting_position());
+        assert!(game.headers.is_empty());
+        assert_eq!(
+            game.starting_position().unwrap().to_fen(),
+            Gamestate::starting_position().to_fen()
+        );
+    }
 }
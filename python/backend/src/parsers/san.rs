@@ -0,0 +1,218 @@
+/*
+ * Author: Renier Barnard
+ * Standard Algebraic Notation (SAN) for moves, e.g. "Nf3", "exd5", "O-O", "e8=Q+"
+ */
+
+use super::{fen_parser::Gamestate, notation::index_to_chess_notation, notation::Move};
+use crate::validation::make_move::{apply_move, classify_move, MoveKind};
+use crate::validation::possible_moves::{get_game_status, legal_moves, legal_moves_to, GameStatus};
+
+/// Writes `mv` in SAN for the position `game`, including the `+` or `#` suffix when
+/// the move gives check or mate. The move is assumed to be legal.
+pub fn move_to_san(game: &Gamestate, mv: &Move) -> String {
+    let kind = classify_move(game, mv.from, mv.to, mv.promotion);
+
+    let mut after = game.clone();
+    apply_move(&mut after, mv);
+    let status = get_game_status(
+        &after.board,
+        after.enpassat.unwrap_or((0, 0)),
+        after.castling,
+        after.player,
+    );
+
+    san_with_status(game, mv, kind, &status)
+}
+
+/// Finds the legal move in `game` written as `san`. Check and mate suffixes and
+/// annotations like `!?` are optional, so `"Nf3"` and `"Nf3+!"` are the same move.
+pub fn san_to_move(game: &Gamestate, san: &str) -> Result<Move, String> {
+    let wanted = strip_suffixes(san);
+    legal_moves(game)
+        .into_iter()
+        .find(|mv| strip_suffixes(&move_to_san(game, mv)) == wanted)
+        .ok_or_else(|| format!("No legal move matches '{}'", san))
+}
+
+fn strip_suffixes(san: &str) -> &str {
+    san.trim().trim_end_matches(['+', '#', '!', '?'])
+}
+
+/// Writes `mv` in SAN given its kind and the status of the position it leads to, for
+/// callers that have already played the move out
+pub(crate) fn san_with_status(
+    game: &Gamestate,
+    mv: &Move,
+    kind: MoveKind,
+    status: &GameStatus,
+) -> String {
+    let mut san = match kind {
+        MoveKind::Castle { kingside: true } => "O-O".to_string(),
+        MoveKind::Castle { kingside: false } => "O-O-O".to_string(),
+        kind => piece_move_san(game, mv, kind),
+    };
+
+    match status {
+        GameStatus::Checkmate => san.push('#'),
+        GameStatus::Check => san.push('+'),
+        _ => (),
+    }
+
+    san
+}
+
+/// SAN for anything but castling, without the check suffix
+fn piece_move_san(game: &Gamestate, mv: &Move, kind: MoveKind) -> String {
+    let piece = game.board[mv.from.0 as usize][mv.from.1 as usize];
+    let from = index_to_chess_notation(mv.from).unwrap_or_default();
+    let to = index_to_chess_notation(mv.to).unwrap_or_default();
+    let capture = matches!(
+        kind,
+        MoveKind::Capture | MoveKind::EnPassant | MoveKind::Promotion { capture: true, .. }
+    );
+
+    let mut san = String::new();
+    if piece.eq_ignore_ascii_case(&'p') {
+        // Pawn captures name the file the pawn came from
+        if capture {
+            san.push_str(&from[..1]);
+        }
+    } else {
+        san.push(piece.to_ascii_uppercase());
+        san.push_str(&disambiguation(game, mv, piece, &from));
+    }
+
+    if capture {
+        san.push('x');
+    }
+    san.push_str(&to);
+
+    if let MoveKind::Promotion { piece, .. } = kind {
+        san.push('=');
+        san.push(piece.to_ascii_uppercase());
+    }
+
+    san
+}
+
+/// The file, rank or both of `from` needed to tell the move apart from the same kind
+/// of piece moving to the same square
+fn disambiguation(game: &Gamestate, mv: &Move, piece: char, from: &str) -> String {
+    let rivals: Vec<(u8, u8)> = legal_moves_to(game, mv.to)
+        .into_iter()
+        .filter(|&square| {
+            square != mv.from && game.board[square.0 as usize][square.1 as usize] == piece
+        })
+        .collect();
+
+    if rivals.is_empty() {
+        String::new()
+    } else if rivals.iter().all(|square| square.1 != mv.from.1) {
+        from[..1].to_string()
+    } else if rivals.iter().all(|square| square.0 != mv.from.0) {
+        from[1..].to_string()
+    } else {
+        from.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn san(fen: &str, mv: Move) -> String {
+        move_to_san(&Gamestate::from_str(fen).unwrap(), &mv)
+    }
+
+    #[test]
+    fn test_san_round_trip_through_a_game() {
+        // Both sides need disambiguation (Nbd7, Nbd2) and castle, Black takes en passant,
+        // White promotes with a capture and Bxh2+ is a check
+        let game_san = [
+            "e4", "d5", "exd5", "c6", "dxc6", "Nf6", "cxb7", "Nbd7", "bxa8=Q", "e5", "d4",
+            "e4", "f4", "exf3", "Nxf3", "Bd6", "Bd3", "O-O", "O-O", "Qc7", "Nbd2", "Nb6",
+            "Qxa7", "Bxh2+", "Kh1", "Ng4",
+        ];
+
+        let mut game = Gamestate::starting_position();
+        for san in game_san {
+            let mv = san_to_move(&game, san).unwrap_or_else(|e| panic!("{}", e));
+            assert_eq!(strip_suffixes(&move_to_san(&game, &mv)), strip_suffixes(san));
+            apply_move(&mut game, &mv);
+        }
+        assert_eq!(
+            game.to_fen(),
+            "2b2rk1/Q1q2ppp/1n6/8/3P2n1/3B1N2/PPPN2Pb/R1BQ1R1K w - - 2 14"
+        );
+    }
+
+    #[test]
+    fn test_san_to_move_rejects_illegal() {
+        let game = Gamestate::starting_position();
+        assert_eq!(san_to_move(&game, "Nf3+").unwrap(), Move::new((7, 6), (5, 5)));
+        assert!(san_to_move(&game, "Nf4").is_err());
+        assert!(san_to_move(&game, "O-O").is_err());
+    }
+
+    #[test]
+    fn test_back_rank_mate() {
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", Move::new((7, 0), (0, 0))), "Ra8#");
+    }
+
+    #[test]
+    fn test_castling_and_promotion_suffixes() {
+        // Castling puts the rook on f1, checking the king on f8
+        assert_eq!(san("5k2/8/8/8/8/8/8/4K2R w K - 0 1", Move::new((7, 4), (7, 6))), "O-O+");
+        assert_eq!(
+            san("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", Move::with_promotion((1, 4), (0, 4), 'q')),
+            "e8=Q+"
+        );
+        assert_eq!(
+            san("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", Move::with_promotion((1, 4), (0, 4), 'n')),
+            "e8=N"
+        );
+    }
+
+    #[test]
+    fn test_promotion_notations_agree() {
+        let white = Gamestate::from_str("k7/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let black = Gamestate::from_str("4k3/8/8/8/8/8/4p3/K7 b - - 0 1").unwrap();
+        for (piece, letter) in [('q', 'Q'), ('r', 'R'), ('b', 'B'), ('n', 'N')] {
+            let mv = Move::with_promotion((1, 4), (0, 4), piece);
+            assert_eq!(mv.to_string(), format!("e7-e8{}", piece));
+            assert_eq!(Move::from_str(&mv.to_string()).unwrap(), mv);
+            let san = move_to_san(&white, &mv);
+            assert_eq!(san.trim_end_matches('+'), format!("e8={}", letter));
+
+            // Black promotions are written with the same letters
+            let mv = Move::with_promotion((6, 4), (7, 4), piece);
+            assert_eq!(mv.to_string(), format!("e2-e1{}", piece));
+            let san = move_to_san(&black, &mv);
+            assert_eq!(san.trim_end_matches('+'), format!("e1={}", letter));
+        }
+
+        // An uppercase letter set directly still displays lowercase
+        let mv = Move {
+            promotion: Some('N'),
+            ..Move::new((1, 4), (0, 4))
+        };
+        assert_eq!(mv.to_string(), "e7-e8n");
+    }
+
+    #[test]
+    fn test_captures_and_disambiguation() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san(start, Move::new((7, 6), (5, 5))), "Nf3");
+        assert_eq!(san(start, Move::new((6, 4), (4, 4))), "e4");
+
+        let knights = "4k3/8/8/3p4/8/2N3N1/8/4K3 w - - 0 1";
+        assert_eq!(san(knights, Move::new((5, 2), (4, 4))), "Nce4");
+        assert_eq!(san(knights, Move::new((5, 2), (3, 3))), "Nxd5");
+
+        let rooks = "4k3/8/R7/8/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san(rooks, Move::new((2, 0), (4, 0))), "R6a4");
+
+        assert_eq!(san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2", Move::new((3, 4), (2, 3))), "exd6");
+    }
+}
@@ -0,0 +1,234 @@
+/*
+ * Author: Renier Barnard
+ * State kept by the CLI between requests on one engine instance
+ */
+
+use crate::parsers::fen_parser::Gamestate;
+use crate::parsers::notation::Move;
+use crate::validation::evaluation::Evaluation;
+use crate::validation::possible_moves::is_threefold_count;
+use std::collections::HashMap;
+
+/// Plies searched for `bestmove` unless the CLI was given `--depth`
+pub const DEFAULT_SEARCH_DEPTH: u32 = 2;
+
+/// Tracks the positions of the game currently being played through the CLI
+#[derive(Debug)]
+pub struct Session {
+    /// Position hashes of the current game, oldest first
+    history: Vec<u64>,
+    /// How many times each position hash occurs in `history`
+    counts: HashMap<u64, u8>,
+    /// Static evaluation used when the engine scores positions
+    evaluation: Evaluation,
+    /// How many plies `bestmove` searches
+    search_depth: u32,
+    /// Whether replies include the material count
+    report_material: bool,
+    /// Whether promotions without a piece ask for one instead of queening
+    interactive_promotion: bool,
+    /// A promotion waiting for its piece, with the position it is played from
+    pending_promotion: Option<(Gamestate, Move)>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            history: Vec::new(),
+            counts: HashMap::new(),
+            evaluation: Evaluation::Material,
+            search_depth: DEFAULT_SEARCH_DEPTH,
+            report_material: false,
+            interactive_promotion: false,
+            pending_promotion: None,
+        }
+    }
+
+    /// Searches `depth` plies deep (at least 1) for the rest of the session
+    pub fn with_search_depth(mut self, depth: u32) -> Self {
+        self.search_depth = depth.max(1);
+        self
+    }
+
+    pub fn search_depth(&self) -> u32 {
+        self.search_depth
+    }
+
+    /// Includes each side's material in move replies for the rest of the session
+    pub fn with_material_report(mut self, report_material: bool) -> Self {
+        self.report_material = report_material;
+        self
+    }
+
+    pub fn reports_material(&self) -> bool {
+        self.report_material
+    }
+
+    /// Asks for the promotion piece instead of queening for the rest of the session
+    pub fn with_interactive_promotion(mut self, interactive_promotion: bool) -> Self {
+        self.interactive_promotion = interactive_promotion;
+        self
+    }
+
+    pub fn interactive_promotion(&self) -> bool {
+        self.interactive_promotion
+    }
+
+    /// Holds `mv` from `game` until the client picks its promotion piece. A later
+    /// promotion replaces one still waiting.
+    pub fn set_pending_promotion(&mut self, game: &Gamestate, mv: Move) {
+        self.pending_promotion = Some((game.clone(), mv));
+    }
+
+    /// Takes the promotion waiting for its piece, if any
+    pub fn take_pending_promotion(&mut self) -> Option<(Gamestate, Move)> {
+        self.pending_promotion.take()
+    }
+
+    /// Uses `evaluation` to score positions for the rest of the session
+    pub fn with_evaluation(mut self, evaluation: Evaluation) -> Self {
+        self.evaluation = evaluation;
+        self
+    }
+
+    pub fn evaluation(&self) -> Evaluation {
+        self.evaluation
+    }
+
+    /// Syncs the session with the position a client sent.
+    ///
+    /// Clients echo back the FEN of the previous reply, so a position matching the last
+    /// one recorded continues the game; anything else starts a fresh history.
+    pub fn sync(&mut self, game: &Gamestate) {
+        let hash = game.position_hash();
+        if self.history.last() != Some(&hash) {
+            self.reset(game);
+        }
+    }
+
+    /// Starts a new game history at `game`
+    pub fn reset(&mut self, game: &Gamestate) {
+        self.history.clear();
+        self.counts.clear();
+        self.push(game.position_hash());
+    }
+
+    /// Records the position reached after a move
+    pub fn record(&mut self, game: &Gamestate) {
+        self.push(game.position_hash());
+    }
+
+    /// Forgets the last recorded position, when its move is taken back. The position
+    /// the history started from is kept. Returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        if self.history.len() <= 1 {
+            return false;
+        }
+        if let Some(hash) = self.history.pop() {
+            if let Some(count) = self.counts.get_mut(&hash) {
+                *count -= 1;
+                if *count == 0 {
+                    self.counts.remove(&hash);
+                }
+            }
+        }
+        true
+    }
+
+    /// Returns how many times `game`'s position has occurred in this session (at least 1)
+    pub fn repetition_count(&self, game: &Gamestate) -> u8 {
+        let count = self.counts.get(&game.position_hash()).copied().unwrap_or(0);
+        count.max(1)
+    }
+
+    /// True once `game`'s position has occurred three times, so a draw can be claimed
+    pub fn draw_claimable(&self, game: &Gamestate) -> bool {
+        is_threefold_count(self.repetition_count(game))
+    }
+
+    fn push(&mut self, hash: u64) {
+        self.history.push(hash);
+        let count = self.counts.entry(hash).or_insert(0);
+        *count = count.saturating_add(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::notation::Move;
+    use crate::validation::make_move::apply_move;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_sync_resets_on_unrelated_position() {
+        let start =
+            Gamestate::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+                .unwrap();
+        let other = Gamestate::from_str("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+
+        let mut session = Session::new();
+        session.sync(&start);
+        session.record(&other);
+        session.record(&start);
+        assert_eq!(session.repetition_count(&start), 2);
+
+        // A position that doesn't follow on from the last one starts over
+        session.sync(&other);
+        assert_eq!(session.repetition_count(&start), 1);
+        assert_eq!(session.repetition_count(&other), 1);
+    }
+
+    #[test]
+    fn test_sync_keeps_history_after_double_push() {
+        let mut game = Gamestate::starting_position();
+        let mut session = Session::new();
+        session.reset(&game);
+        apply_move(&mut game, &Move::new((6, 4), (4, 4))); // e2-e4
+        session.record(&game);
+
+        // The reply's FEN leaves out e3, as no black pawn can take there
+        let echoed = Gamestate::from_str(&game.to_fen()).unwrap();
+        assert_eq!(echoed.enpassat, None);
+        assert_ne!(game.enpassat, None);
+        session.sync(&echoed);
+        assert_eq!(session.history.len(), 2);
+    }
+
+    #[test]
+    fn test_knight_shuffle_threefold() {
+        let mut game =
+            Gamestate::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+                .unwrap();
+        let start = game.clone();
+        let shuffle = [
+            ((7, 6), (5, 5)), // Ng1-f3
+            ((0, 6), (2, 5)), // Ng8-f6
+            ((5, 5), (7, 6)), // Nf3-g1
+            ((2, 5), (0, 6)), // Nf6-g8
+        ];
+
+        let mut session = Session::new();
+        session.reset(&game);
+        for _ in 0..2 {
+            assert!(!session.draw_claimable(&start));
+            for (from, to) in shuffle {
+                apply_move(&mut game, &Move::new(from, to));
+                session.record(&game);
+            }
+        }
+        assert_eq!(session.repetition_count(&start), 3);
+        assert!(session.draw_claimable(&start));
+
+        // Taking the last move back brings the count down again
+        assert!(session.undo());
+        assert_eq!(session.repetition_count(&start), 2);
+        assert!(!session.draw_claimable(&start));
+    }
+}
@@ -0,0 +1,122 @@
+/*
+ * Author: Renier Barnard
+ * Legal moves annotated with everything a move list display needs
+ */
+
+use super::evaluation::evaluate_material;
+use super::make_move::{apply_move, classify_move, MoveKind};
+use super::possible_moves::{checking_pieces, get_game_status, legal_moves, GameStatus};
+use crate::parsers::{fen_parser::Gamestate, san::san_with_status};
+use serde::Serialize;
+
+/// A legal move with its notation and flags
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AnnotatedMove {
+    /// Dash notation, e.g. `e2-e4` or `e7-e8q`
+    pub coordinate: String,
+    pub san: String,
+    /// True for en passant too, where the destination square is empty
+    pub is_capture: bool,
+    /// True for checkmate as well as plain check
+    pub is_check: bool,
+    /// True when a piece other than the one that moved gives check, i.e. the move
+    /// uncovered a line onto the king. Castling checks count as direct.
+    pub is_discovered_check: bool,
+    pub is_castle: bool,
+    pub is_en_passant: bool,
+    /// Lowercase promotion piece, if the move promotes
+    pub promotion: Option<char>,
+    /// Material balance after the move, in centipawns from White's point of view
+    pub eval: i32,
+}
+
+/// Returns every legal move for the side to move, annotated. Each move is played out
+/// once on a copy of the game, and the flags, SAN suffix and eval all come from that.
+pub fn annotated_legal_moves(game: &Gamestate) -> Vec<AnnotatedMove> {
+    legal_moves(game)
+        .into_iter()
+        .map(|mv| {
+            let kind = classify_move(game, mv.from, mv.to, mv.promotion);
+
+            let mut after = game.clone();
+            apply_move(&mut after, &mv);
+            let status = get_game_status(
+                &after.board,
+                after.enpassat.unwrap_or((0, 0)),
+                after.castling,
+                after.player,
+            );
+
+            let is_check = matches!(status, GameStatus::Check | GameStatus::Checkmate);
+            let is_castle = matches!(kind, MoveKind::Castle { .. });
+            let is_discovered_check = is_check
+                && !is_castle
+                && checking_pieces(&after).iter().any(|&square| square != mv.to);
+
+            AnnotatedMove {
+                coordinate: mv.to_string(),
+                san: san_with_status(game, &mv, kind, &status),
+                is_capture: matches!(
+                    kind,
+                    MoveKind::Capture
+                        | MoveKind::EnPassant
+                        | MoveKind::Promotion { capture: true, .. }
+                ),
+                is_check,
+                is_discovered_check,
+                is_castle,
+                is_en_passant: kind == MoveKind::EnPassant,
+                promotion: mv.promotion,
+                eval: evaluate_material(&after.board),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_capture_and_check_flags() {
+        // Scholar's mate: Qxf7# captures with check
+        let game = Gamestate::from_str(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+        )
+        .unwrap();
+        let moves = annotated_legal_moves(&game);
+
+        let mate = moves.iter().find(|m| m.coordinate == "h5-f7").unwrap();
+        assert_eq!(mate.san, "Qxf7#");
+        assert!(mate.is_capture && mate.is_check);
+        assert!(!mate.is_castle && !mate.is_en_passant);
+        assert_eq!(mate.eval, 100);
+
+        let quiet = moves.iter().find(|m| m.coordinate == "a2-a3").unwrap();
+        assert_eq!(quiet.san, "a3");
+        assert!(!quiet.is_capture && !quiet.is_check);
+        assert_eq!(quiet.eval, 0);
+    }
+
+    #[test]
+    fn test_discovered_check_flag() {
+        // The e4 knight screens the e1 rook from the king on e8
+        let game = Gamestate::from_str("4k3/8/8/8/4N3/8/8/1Q2R1K1 w - - 0 1").unwrap();
+        let moves = annotated_legal_moves(&game);
+        let find = |coordinate: &str| moves.iter().find(|m| m.coordinate == coordinate).unwrap();
+
+        let discovered = find("e4-c5");
+        assert!(discovered.is_check && discovered.is_discovered_check);
+
+        // Nf6 checks directly as well as uncovering the rook
+        let double = find("e4-f6");
+        assert!(double.is_check && double.is_discovered_check);
+
+        let direct = find("b1-b5");
+        assert!(direct.is_check && !direct.is_discovered_check);
+
+        let quiet = find("g1-h1");
+        assert!(!quiet.is_check && !quiet.is_discovered_check);
+    }
+}
@@ -0,0 +1,200 @@
+/*
+ * Author: Renier Barnard
+ * Attack generation, as opposed to move generation:
+ * - Pawns only attack diagonally (their pushes never attack)
+ * - A square holding a piece of the attacker's own color still counts as attacked (defended)
+ */
+
+use super::pieces::{occupied_squares, Color, Piece};
+use std::collections::HashSet;
+
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (2, 1),
+    (2, -1),
+    (-2, 1),
+    (-2, -1),
+    (1, 2),
+    (1, -2),
+    (-1, 2),
+    (-1, -2),
+];
+
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (0, 1),
+    (0, -1),
+    (1, 0),
+    (-1, 0),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+const ORTHOGONAL: [(i8, i8); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+const DIAGONAL: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+fn offset(square: (u8, u8), (dx, dy): (i8, i8)) -> Option<(u8, u8)> {
+    let x = square.0.checked_add_signed(dx)?;
+    let y = square.1.checked_add_signed(dy)?;
+    (x < 8 && y < 8).then_some((x, y))
+}
+
+/// Returns every square attacked by the piece on `from`, including squares
+/// occupied by its own side. Returns nothing for an empty square.
+pub fn attacked_squares(from: (u8, u8), board: &[[char; 8]; 8]) -> Vec<(u8, u8)> {
+    let piece = board[from.0 as usize][from.1 as usize];
+    let (Some(kind), Some(color)) = (Piece::from_char(piece), Color::of(piece)) else {
+        return Vec::new();
+    };
+
+    let steps = |offsets: &[(i8, i8)]| -> Vec<(u8, u8)> {
+        offsets.iter().filter_map(|&d| offset(from, d)).collect()
+    };
+    let rays = |directions: &[(i8, i8)]| -> Vec<(u8, u8)> {
+        let mut squares = Vec::new();
+        for &direction in directions {
+            let mut current = from;
+            while let Some(next) = offset(current, direction) {
+                squares.push(next);
+                if board[next.0 as usize][next.1 as usize] != ' ' {
+                    break; // The first piece hit blocks the rest of the ray
+                }
+                current = next;
+            }
+        }
+        squares
+    };
+
+    match kind {
+        // White pawns move up the board (row decreases), black pawns down
+        Piece::Pawn => match color {
+            Color::White => steps(&[(-1, -1), (-1, 1)]),
+            Color::Black => steps(&[(1, -1), (1, 1)]),
+        },
+        Piece::Knight => steps(&KNIGHT_OFFSETS),
+        Piece::King => steps(&KING_OFFSETS),
+        Piece::Bishop => rays(&DIAGONAL),
+        Piece::Rook => rays(&ORTHOGONAL),
+        Piece::Queen => {
+            let mut squares = rays(&ORTHOGONAL);
+            squares.extend(rays(&DIAGONAL));
+            squares
+        }
+    }
+}
+
+/// Every piece of `color` attacking `square`, with its type and the square it stands on.
+/// Pawns count only through their diagonal attacks.
+pub fn attackers_by_type(
+    square: (u8, u8),
+    board: &[[char; 8]; 8],
+    color: Color,
+) -> Vec<(Piece, (u8, u8))> {
+    occupied_squares(board)
+        .filter(|&(_, piece)| Color::of(piece) == Some(color))
+        .filter(|&(from, _)| attacked_squares(from, board).contains(&square))
+        .filter_map(|(from, piece)| Some((Piece::from_char(piece)?, from)))
+        .collect()
+}
+
+/// Every square attacked by at least one piece of `color`, as a set. `AttackTable` has
+/// the per-square counts.
+pub fn controlled_squares(board: &[[char; 8]; 8], color: Color) -> HashSet<(u8, u8)> {
+    occupied_squares(board)
+        .filter(|&(_, piece)| Color::of(piece) == Some(color))
+        .flat_map(|(square, _)| attacked_squares(square, board))
+        .collect()
+}
+
+/// How many pieces of one color attack each square of a position.
+///
+/// Building the table costs one attack scan per attacking piece. Checking king
+/// destinations against it is then a lookup, instead of rescanning the whole board
+/// for every square the king might step to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttackTable {
+    counts: [[u8; 8]; 8],
+}
+
+impl AttackTable {
+    /// Builds the table of squares attacked by `attacker` on `board`
+    pub fn new(board: &[[char; 8]; 8], attacker: Color) -> Self {
+        let mut counts = [[0u8; 8]; 8];
+        for (square, piece) in occupied_squares(board) {
+            if Color::of(piece) != Some(attacker) {
+                continue;
+            }
+            for (x, y) in attacked_squares(square, board) {
+                counts[x as usize][y as usize] += 1;
+            }
+        }
+        Self { counts }
+    }
+
+    /// Number of attacker pieces hitting `square`
+    pub fn count(&self, square: (u8, u8)) -> u8 {
+        self.counts[square.0 as usize][square.1 as usize]
+    }
+
+    pub fn is_attacked(&self, square: (u8, u8)) -> bool {
+        self.count(square) > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pawn_attacks_diagonally_only() {
+        let mut board = [[' '; 8]; 8];
+        board[6][4] = 'P'; // White pawn at e2
+        board[1][0] = 'p'; // Black pawn at a7
+
+        let mut white = attacked_squares((6, 4), &board);
+        white.sort();
+        assert_eq!(white, vec![(5, 3), (5, 5)]); // d3, f3
+        assert_eq!(attacked_squares((1, 0), &board), vec![(2, 1)]); // b6
+    }
+
+    #[test]
+    fn test_table_counts_defended_and_multiple_attackers() {
+        let mut board = [[' '; 8]; 8];
+        board[7][0] = 'R'; // a1
+        board[7][7] = 'R'; // h1
+        board[7][4] = 'N'; // e1, blocks both rooks
+
+        let table = AttackTable::new(&board, Color::White);
+        assert_eq!(table.count((7, 4)), 2); // Knight defended by both rooks
+        assert_eq!(table.count((7, 5)), 1); // f1 by the h1 rook
+        assert!(table.is_attacked((6, 2))); // c2 by the knight
+        assert_eq!(AttackTable::new(&board, Color::Black).counts, [[0; 8]; 8]);
+    }
+
+    #[test]
+    fn test_attackers_by_type() {
+        let mut board = [[' '; 8]; 8];
+        board[4][4] = 'p'; // e4, the target
+        board[5][2] = 'N'; // c3
+        board[7][1] = 'B'; // b1, along the b1-h7 diagonal
+        board[5][4] = 'P'; // e3, pushes onto e4 but does not attack it
+
+        let attackers = attackers_by_type((4, 4), &board, Color::White);
+        assert_eq!(attackers, vec![(Piece::Knight, (5, 2)), (Piece::Bishop, (7, 1))]);
+        assert!(attackers_by_type((4, 4), &board, Color::Black).is_empty());
+    }
+
+    #[test]
+    fn test_controlled_squares() {
+        let mut board = [[' '; 8]; 8];
+        board[6][4] = 'P'; // e2
+        board[7][6] = 'N'; // g1, also covering f3
+        let squares = controlled_squares(&board, Color::White);
+        let mut expected = vec![(5, 3), (5, 5), (5, 7), (6, 4)];
+        expected.sort();
+        let mut squares: Vec<_> = squares.into_iter().collect();
+        squares.sort();
+        assert_eq!(squares, expected);
+        assert!(controlled_squares(&board, Color::Black).is_empty());
+    }
+}
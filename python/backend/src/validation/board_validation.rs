@@ -1,6 +1,37 @@
 // Author: Renier Barnard
 // Fixed: Changed from panic to Result for better error handling
 
+use super::pieces::Color;
+use super::possible_moves::is_in_check;
+use crate::parsers::fen_parser::Gamestate;
+use std::fmt;
+
+/// Why a position cannot occur in a game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardError {
+    /// The board itself is impossible, with the reason from `validate_board`
+    InvalidBoard(String),
+    /// The side that just moved has left its king in check
+    OpponentInCheck,
+    /// The en passant square does not follow a double step
+    InvalidEnPassant(String),
+    /// A castling right is held although the king or rook has left its square
+    InvalidCastling(String),
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardError::InvalidBoard(reason)
+            | BoardError::InvalidEnPassant(reason)
+            | BoardError::InvalidCastling(reason) => write!(f, "{}", reason),
+            BoardError::OpponentInCheck => write!(f, "The side not to move is in check"),
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
+
 pub fn validate_board(board: &[[char; 8]; 8]) -> Result<(), String> {
     let mut kings: (i8, i8) = (0, 0);
     let mut pawns: (i8, i8) = (0, 0);
@@ -17,44 +48,42 @@ pub fn validate_board(board: &[[char; 8]; 8]) -> Result<(), String> {
         }
     };
 
-    for rank in 0..8 {
-        for file in 0..8 {
+    for (rank, row) in board.iter().enumerate() {
+        for (file, &square) in row.iter().enumerate() {
             // Check for illegal pawn positions (pawns can't be on ranks 1 or 8)
-            if [0, 7].contains(&rank) {
-                if ['p', 'P'].contains(&board[rank][file]) {
-                    return Err(format!("Illegal pawn position at rank {} file {}", rank, file));
-                }
+            if [0, 7].contains(&rank) && ['p', 'P'].contains(&square) {
+                return Err(format!("Illegal pawn position at rank {} file {}", rank, file));
             }
             
             // Count pieces
-            match board[rank][file] {
+            match square {
                 ' ' => continue,
-                'p' | 'P' => match player(board[rank][file]) {
+                'p' | 'P' => match player(square) {
                     0 => pawns.0 += 1,
                     1 => pawns.1 += 1,
                     _ => return Err("Invalid player value".to_string()),
                 },
-                'r' | 'R' => match player(board[rank][file]) {
+                'r' | 'R' => match player(square) {
                     0 => rooks.0 += 1,
                     1 => rooks.1 += 1,
                     _ => return Err("Invalid player value".to_string()),
                 },
-                'n' | 'N' => match player(board[rank][file]) {
+                'n' | 'N' => match player(square) {
                     0 => knights.0 += 1,
                     1 => knights.1 += 1,
                     _ => return Err("Invalid player value".to_string()),
                 },
-                'b' | 'B' => match player(board[rank][file]) {
+                'b' | 'B' => match player(square) {
                     0 => bishops.0 += 1,
                     1 => bishops.1 += 1,
                     _ => return Err("Invalid player value".to_string()),
                 },
-                'q' | 'Q' => match player(board[rank][file]) {
+                'q' | 'Q' => match player(square) {
                     0 => queens.0 += 1,
                     1 => queens.1 += 1,
                     _ => return Err("Invalid player value".to_string()),
                 },
-                'k' | 'K' => match player(board[rank][file]) {
+                'k' | 'K' => match player(square) {
                     0 => kings.0 += 1,
                     1 => kings.1 += 1,
                     _ => return Err("Invalid player value".to_string()),
@@ -77,7 +106,7 @@ pub fn validate_board(board: &[[char; 8]; 8]) -> Result<(), String> {
     // Check for too many promoted pieces
     let mut promotable: (i8, i8) = (8 - pawns.0, 8 - pawns.1);
 
-    for &(limit, ref counts) in &[(2, &rooks), (2, &knights), (2, &bishops), (1, &queens)] {
+    for &(limit, counts) in &[(2, &rooks), (2, &knights), (2, &bishops), (1, &queens)] {
         if counts.0 > limit {
             promotable.0 = promotable.0.saturating_sub(counts.0 - limit);
         }
@@ -93,5 +122,167 @@ pub fn validate_board(board: &[[char; 8]; 8]) -> Result<(), String> {
         return Err("Too many promoted pieces for black".to_string());
     }
 
+    // A pawn only changes file by capturing, so every pawn beyond the first on a file
+    // needs at least one enemy piece to have been taken
+    let totals = [
+        kings.0 + pawns.0 + queens.0 + knights.0 + rooks.0 + bishops.0,
+        kings.1 + pawns.1 + queens.1 + knights.1 + rooks.1 + bishops.1,
+    ];
+    for (side, (pawn, enemy)) in [('P', 1), ('p', 0)].into_iter().enumerate() {
+        let doubled: i8 = (0..8)
+            .map(|file| board.iter().filter(|row| row[file] == pawn).count() as i8)
+            .map(|count| (count - 1).max(0))
+            .sum();
+        let captured = 16 - totals[enemy];
+        if doubled > captured {
+            let (name, enemy_name) = match side {
+                0 => ("white", "black"),
+                _ => ("black", "white"),
+            };
+            return Err(format!(
+                "Too many doubled pawns for {}: {} need a capture each but {} has lost {} pieces",
+                name, doubled, enemy_name, captured
+            ));
+        }
+    }
+
     Ok(())
 }
+
+/// Validates a whole position: the board as in `validate_board`, the side not to move
+/// not being in check, the en passant square against the side to move, and castling
+/// rights against the king and the rooks they name (see `Gamestate::castling_rook`).
+pub fn validate_position(game: &Gamestate) -> Result<(), BoardError> {
+    validate_board(&game.board).map_err(BoardError::InvalidBoard)?;
+
+    let opponent = Color::from_player(game.player).opposite().to_player();
+    if is_in_check(&game.board, (0, 0), game.castling, opponent) {
+        return Err(BoardError::OpponentInCheck);
+    }
+
+    validate_en_passant(game).map_err(BoardError::InvalidEnPassant)?;
+    validate_castling(game).map_err(BoardError::InvalidCastling)
+}
+
+/// Each castling right needs the king on its back rank and the rook the right names on
+/// that side of it, so Chess960 starting squares pass as well as the standard ones
+fn validate_castling(game: &Gamestate) -> Result<(), String> {
+    let rights = game.castling_rights();
+    let checks = [
+        (rights.white_king, Color::White, "kingside", true),
+        (rights.white_queen, Color::White, "queenside", false),
+        (rights.black_king, Color::Black, "kingside", true),
+        (rights.black_queen, Color::Black, "queenside", false),
+    ];
+    for (held, color, side, kingside) in checks {
+        if held && game.castling_rook(color, kingside).is_none() {
+            return Err(format!(
+                "{:?} holds the {} castling right but its king or rook has moved",
+                color, side
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_en_passant(game: &Gamestate) -> Result<(), String> {
+    if let Some((row, file)) = game.enpassat {
+        // With White to move, Black just double-stepped: the target is on rank 6 (row 2)
+        // with the black pawn in front of it on rank 5. The mirror image for Black.
+        let (target_row, pawn_row, pawn) = match game.player {
+            'w' => (2, 3, 'p'),
+            _ => (5, 4, 'P'),
+        };
+        if row != target_row {
+            return Err(format!(
+                "En passant square on rank {} but {} is to move",
+                8 - row,
+                if game.player == 'w' { "white" } else { "black" }
+            ));
+        }
+        if game.board[pawn_row][file as usize] != pawn
+            || game.board[row as usize][file as usize] != ' '
+        {
+            return Err("En passant square does not follow a pawn double step".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn validate(fen: &str) -> Result<(), String> {
+        validate_position(&Gamestate::from_str(fen).unwrap()).map_err(|e| e.to_string())
+    }
+
+    fn error(fen: &str) -> BoardError {
+        validate_position(&Gamestate::from_str(fen).unwrap()).unwrap_err()
+    }
+
+    #[test]
+    fn test_board_error_variants() {
+        assert!(validate("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_ok());
+        assert!(matches!(
+            error("4k3/8/8/8/8/8/8/4K1K1 w - - 0 1"),
+            BoardError::InvalidBoard(_)
+        ));
+        // White to move, but the black king is already in check from the rook
+        assert_eq!(error("4k3/8/8/8/8/8/8/4RK2 w - - 0 1"), BoardError::OpponentInCheck);
+        assert!(matches!(
+            error("4k3/8/8/8/8/8/8/4K3 w - d6 0 1"),
+            BoardError::InvalidEnPassant(_)
+        ));
+        // The h1 rook is gone but White still claims kingside castling
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/R3K3 w KQ - 0 1"),
+            BoardError::InvalidCastling(
+                "White holds the kingside castling right but its king or rook has moved"
+                    .to_string()
+            )
+        );
+        assert!(matches!(error("4k2r/8/8/8/8/8/8/4K3 w q - 0 1"), BoardError::InvalidCastling(_)));
+        assert!(validate("r3k3/8/8/8/8/8/8/R3K3 w Qq - 0 1").is_ok());
+        // Chess960: the king on g1 with its rooks on f1 and h1
+        assert!(validate("5rkr/8/8/8/8/8/8/5RKR w KQkq - 0 1").is_ok());
+        assert!(matches!(
+            error("5rkr/8/8/8/8/8/8/5RK1 w KQkq - 0 1"),
+            BoardError::InvalidCastling(_)
+        ));
+        // An X-FEN file right needs a rook on that file
+        assert!(matches!(error("4k3/8/8/8/8/8/8/4K2R w F - 0 1"), BoardError::InvalidCastling(_)));
+    }
+
+    #[test]
+    fn test_en_passant_rank_matches_side_to_move() {
+        assert!(validate("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3").is_ok());
+        assert!(validate("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").is_ok());
+
+        // White to move, but the target is on rank 3 as if White had just moved
+        let err = validate("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e3 0 1");
+        assert!(err.unwrap_err().contains("rank 3"));
+    }
+
+    #[test]
+    fn test_doubled_pawns_need_captures() {
+        // Four pawns on the e-file take three captures, but Black has all sixteen pieces
+        let err = validate("rnbqkbnr/pppppppp/4P3/4P3/4P3/4P3/8/RNBQKBNR w KQkq - 0 1");
+        assert!(err.unwrap_err().contains("Too many doubled pawns for white"));
+
+        // With four black pieces gone it is possible
+        assert!(validate("4kbnr/pppppppp/4P3/4P3/4P3/4P3/8/RNBQKBNR w K - 0 1").is_ok());
+
+        // Exactly three gone is just enough, two is one short
+        assert!(validate("3qkbnr/pppppppp/4P3/4P3/4P3/4P3/8/RNBQKBNR w K - 0 1").is_ok());
+        let err = validate("2bqkbnr/pppppppp/4P3/4P3/4P3/4P3/8/RNBQKBNR w K - 0 1");
+        assert!(err.unwrap_err().contains("3 need a capture each but black has lost 2 pieces"));
+    }
+
+    #[test]
+    fn test_en_passant_needs_double_stepped_pawn() {
+        assert!(validate("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq d6 0 1").is_err());
+    }
+}
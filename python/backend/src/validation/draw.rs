@@ -0,0 +1,110 @@
+/*
+ * Author: Renier Barnard
+ * Draws decided by the material left on the board
+ *
+ * Automatic (dead position, no sequence of legal moves can mate):
+ * - K vs K
+ * - K + one minor piece vs K
+ * - Only bishops besides the kings, all on squares of one color (e.g. K+B vs K+B, same colors)
+ *
+ * Heuristic (mate can be constructed but cannot be forced, so the game is a practical draw):
+ * - K + two knights vs K
+ * - K + minor vs K + minor, any bishop colors
+ */
+
+use super::pieces::{occupied_squares, Color, Piece};
+
+/// What the material on the board says about the game ending in a draw
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaterialVerdict {
+    /// Enough material for at least one side to force or stumble into mate
+    Sufficient,
+    /// No legal sequence of moves can end in mate: the game is drawn
+    DeadPosition,
+    /// Mate is possible only with the loser's help; a draw flag, not a forced result
+    PracticalDraw,
+}
+
+/// Judges the material on `board` for insufficient-material and dead-position draws
+pub fn material_verdict(board: &[[char; 8]; 8]) -> MaterialVerdict {
+    // (piece, color, square color) for every minor piece
+    let mut minors: Vec<(Piece, Color, usize)> = Vec::new();
+    for ((rank, file), piece) in occupied_squares(board) {
+        match Piece::from_char(piece) {
+            Some(Piece::King) => (),
+            Some(kind @ (Piece::Knight | Piece::Bishop)) => {
+                if let Some(color) = Color::of(piece) {
+                    minors.push((kind, color, (rank + file) as usize % 2));
+                }
+            }
+            // Any pawn, rook or queen is enough to play on
+            _ => return MaterialVerdict::Sufficient,
+        }
+    }
+
+    let count = |color: Color| minors.iter().filter(|m| m.1 == color).count();
+    let knights = |color: Color| {
+        minors
+            .iter()
+            .filter(|m| m.1 == color && m.0 == Piece::Knight)
+            .count()
+    };
+    let (white, black) = (count(Color::White), count(Color::Black));
+
+    let same_color_bishops = minors.iter().all(|m| m.0 == Piece::Bishop)
+        && minors.windows(2).all(|pair| pair[0].2 == pair[1].2);
+
+    if minors.len() <= 1 || same_color_bishops {
+        MaterialVerdict::DeadPosition
+    } else if (white == 1 && black == 1)
+        || (black == 0 && knights(Color::White) == 2 && white == 2)
+        || (white == 0 && knights(Color::Black) == 2 && black == 2)
+    {
+        MaterialVerdict::PracticalDraw
+    } else {
+        MaterialVerdict::Sufficient
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::fen_parser::Gamestate;
+    use std::str::FromStr;
+
+    fn verdict(fen: &str) -> MaterialVerdict {
+        material_verdict(&Gamestate::from_str(fen).unwrap().board)
+    }
+
+    #[test]
+    fn test_dead_positions() {
+        assert_eq!(verdict("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), MaterialVerdict::DeadPosition);
+        assert_eq!(verdict("4k3/8/8/8/8/8/8/3NK3 w - - 0 1"), MaterialVerdict::DeadPosition);
+        assert_eq!(verdict("4kb2/8/8/8/8/8/8/4K3 w - - 0 1"), MaterialVerdict::DeadPosition);
+        // Bishops on c1 and f8 both stand on dark squares
+        assert_eq!(verdict("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1"), MaterialVerdict::DeadPosition);
+    }
+
+    #[test]
+    fn test_practical_draws() {
+        assert_eq!(verdict("4k3/8/8/8/8/8/8/1N2K1N1 w - - 0 1"), MaterialVerdict::PracticalDraw);
+        assert_eq!(verdict("1n2k1n1/8/8/8/8/8/8/4K3 w - - 0 1"), MaterialVerdict::PracticalDraw);
+        // Opposite-colored bishops, bishop vs knight, knight vs knight
+        assert_eq!(verdict("4k1b1/8/8/8/8/8/8/2B1K3 w - - 0 1"), MaterialVerdict::PracticalDraw);
+        assert_eq!(verdict("4kn2/8/8/8/8/8/8/2B1K3 w - - 0 1"), MaterialVerdict::PracticalDraw);
+        assert_eq!(verdict("4kn2/8/8/8/8/8/8/3NK3 w - - 0 1"), MaterialVerdict::PracticalDraw);
+    }
+
+    #[test]
+    fn test_sufficient_material() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(verdict(start), MaterialVerdict::Sufficient);
+        assert_eq!(verdict("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"), MaterialVerdict::Sufficient);
+        assert_eq!(verdict("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"), MaterialVerdict::Sufficient);
+        // Bishop and knight, or bishops on both colors, can force mate
+        assert_eq!(verdict("4k3/8/8/8/8/8/8/2B1KN2 w - - 0 1"), MaterialVerdict::Sufficient);
+        assert_eq!(verdict("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1"), MaterialVerdict::Sufficient);
+        // Two knights with the lone king's side still holding a piece
+        assert_eq!(verdict("4kn2/8/8/8/8/8/8/1N2K1N1 w - - 0 1"), MaterialVerdict::Sufficient);
+    }
+}
@@ -0,0 +1,276 @@
+/*
+ * Author: Renier Barnard
+ * Static evaluation of positions, in centipawns from White's point of view
+ */
+
+use super::attacks::controlled_squares;
+use super::pieces::{find_king, occupied_squares, piece_count, Color, Piece};
+use super::possible_moves::get_legal_moves;
+use crate::parsers::fen_parser::Gamestate;
+
+/// Which static evaluation to use when scoring positions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Evaluation {
+    /// Material only (`evaluate_material`)
+    #[default]
+    Material,
+    /// Material plus piece-square tables (`evaluate_positional`)
+    Positional,
+}
+
+impl Evaluation {
+    /// Scores `board` in centipawns from White's point of view
+    pub fn evaluate(self, board: &[[char; 8]; 8]) -> i32 {
+        match self {
+            Evaluation::Material => evaluate_material(board),
+            Evaluation::Positional => evaluate_positional(board),
+        }
+    }
+}
+
+// Piece-square tables from White's side of the board: row 0 is rank 8, as on the
+// board itself. Black pieces read them with the rows flipped. The values are the
+// well-known "simplified evaluation function" tables.
+type Table = [[i32; 8]; 8];
+
+const PAWN_TABLE: Table = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [50, 50, 50, 50, 50, 50, 50, 50],
+    [10, 10, 20, 30, 30, 20, 10, 10],
+    [5, 5, 10, 25, 25, 10, 5, 5],
+    [0, 0, 0, 20, 20, 0, 0, 0],
+    [5, -5, -10, 0, 0, -10, -5, 5],
+    [5, 10, 10, -20, -20, 10, 10, 5],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+];
+
+const KNIGHT_TABLE: Table = [
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+    [-40, -20, 0, 0, 0, 0, -20, -40],
+    [-30, 0, 10, 15, 15, 10, 0, -30],
+    [-30, 5, 15, 20, 20, 15, 5, -30],
+    [-30, 0, 15, 20, 20, 15, 0, -30],
+    [-30, 5, 10, 15, 15, 10, 5, -30],
+    [-40, -20, 0, 5, 5, 0, -20, -40],
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+];
+
+const BISHOP_TABLE: Table = [
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+    [-10, 0, 0, 0, 0, 0, 0, -10],
+    [-10, 0, 5, 10, 10, 5, 0, -10],
+    [-10, 5, 5, 10, 10, 5, 5, -10],
+    [-10, 0, 10, 10, 10, 10, 0, -10],
+    [-10, 10, 10, 10, 10, 10, 10, -10],
+    [-10, 5, 0, 0, 0, 0, 5, -10],
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+];
+
+const ROOK_TABLE: Table = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [5, 10, 10, 10, 10, 10, 10, 5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [0, 0, 0, 5, 5, 0, 0, 0],
+];
+
+const QUEEN_TABLE: Table = [
+    [-20, -10, -10, -5, -5, -10, -10, -20],
+    [-10, 0, 0, 0, 0, 0, 0, -10],
+    [-10, 0, 5, 5, 5, 5, 0, -10],
+    [-5, 0, 5, 5, 5, 5, 0, -5],
+    [0, 0, 5, 5, 5, 5, 0, -5],
+    [-10, 5, 5, 5, 5, 5, 0, -10],
+    [-10, 0, 5, 0, 0, 0, 0, -10],
+    [-20, -10, -10, -5, -5, -10, -10, -20],
+];
+
+/// Middlegame king: stay castled behind the pawns
+const KING_MIDDLEGAME_TABLE: Table = [
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-20, -30, -30, -40, -40, -30, -30, -20],
+    [-10, -20, -20, -20, -20, -20, -20, -10],
+    [20, 20, 0, 0, 0, 0, 20, 20],
+    [20, 30, 10, 0, 0, 10, 30, 20],
+];
+
+/// Endgame king: come to the centre
+const KING_ENDGAME_TABLE: Table = [
+    [-50, -40, -30, -20, -20, -30, -40, -50],
+    [-30, -20, -10, 0, 0, -10, -20, -30],
+    [-30, -10, 20, 30, 30, 20, -10, -30],
+    [-30, -10, 30, 40, 40, 30, -10, -30],
+    [-30, -10, 30, 40, 40, 30, -10, -30],
+    [-30, -10, 20, 30, 30, 20, -10, -30],
+    [-30, -30, 0, 0, 0, 0, -30, -30],
+    [-50, -30, -30, -30, -30, -30, -30, -50],
+];
+
+/// Total value of `color`'s pieces, using `Piece::value`
+pub fn material_of(board: &[[char; 8]; 8], color: Color) -> i32 {
+    Piece::ALL
+        .iter()
+        .map(|&piece| piece_count(board, piece, color) as i32 * piece.value())
+        .sum()
+}
+
+/// Material balance: White's piece values minus Black's, using `Piece::value`
+pub fn evaluate_material(board: &[[char; 8]; 8]) -> i32 {
+    Piece::ALL
+        .iter()
+        .map(|&piece| {
+            let white = piece_count(board, piece, Color::White) as i32;
+            let black = piece_count(board, piece, Color::Black) as i32;
+            (white - black) * piece.value()
+        })
+        .sum()
+}
+
+/// Material plus piece-square-table bonuses, in centipawns from White's point of view.
+/// The king uses its endgame table once `Gamestate::is_endgame` holds for the board.
+pub fn evaluate_positional(board: &[[char; 8]; 8]) -> i32 {
+    let endgame = Gamestate {
+        board: *board,
+        ..Gamestate::new()
+    }
+    .is_endgame();
+
+    let mut score = evaluate_material(board);
+    for ((rank, file), piece) in occupied_squares(board) {
+        let (Some(kind), Some(color)) = (Piece::from_char(piece), Color::of(piece)) else {
+            continue;
+        };
+        let table = match kind {
+            Piece::Pawn => &PAWN_TABLE,
+            Piece::Knight => &KNIGHT_TABLE,
+            Piece::Bishop => &BISHOP_TABLE,
+            Piece::Rook => &ROOK_TABLE,
+            Piece::Queen => &QUEEN_TABLE,
+            Piece::King if endgame => &KING_ENDGAME_TABLE,
+            Piece::King => &KING_MIDDLEGAME_TABLE,
+        };
+        let (rank, file) = (rank as usize, file as usize);
+        match color {
+            Color::White => score += table[rank][file],
+            Color::Black => score -= table[7 - rank][file],
+        }
+    }
+    score
+}
+
+/// Counts the squares next to `color`'s king that the other side attacks, from 0 to 8.
+/// Higher is less safe. Returns 0 if `color` has no king.
+pub fn king_safety(game: &Gamestate, color: Color) -> u8 {
+    let Some((rank, file)) = find_king(&game.board, color) else {
+        return 0;
+    };
+    let enemy = controlled_squares(&game.board, color.opposite());
+
+    let mut attacked = 0;
+    for dr in -1i8..=1 {
+        for df in -1i8..=1 {
+            let (Some(r), Some(f)) = (rank.checked_add_signed(dr), file.checked_add_signed(df))
+            else {
+                continue;
+            };
+            if (dr, df) != (0, 0) && r < 8 && f < 8 && enemy.contains(&(r, f)) {
+                attacked += 1;
+            }
+        }
+    }
+    attacked
+}
+
+/// Number of legal moves for White and for Black.
+///
+/// The side not to move is counted as if it were its turn, without an en passant
+/// capture (that right belongs to the side to move). Good enough for a mobility
+/// heuristic, even though that side could not actually play those moves now.
+pub fn mobility(game: &Gamestate) -> (u32, u32) {
+    let count = |player: char| {
+        let enpassat = if player == game.player {
+            game.enpassat.unwrap_or((0, 0))
+        } else {
+            (0, 0)
+        };
+        get_legal_moves(&game.board, enpassat, game.castling, player).len() as u32
+    };
+    (count('w'), count('b'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_evaluate_material() {
+        assert_eq!(evaluate_material(&Gamestate::starting_position().board), 0);
+
+        // White is a rook up, black has an extra pawn
+        let game = Gamestate::from_str("4k3/pp6/8/8/8/8/P7/R3K3 w - - 0 1").unwrap();
+        assert_eq!(evaluate_material(&game.board), 500 - 100);
+    }
+
+    #[test]
+    fn test_mobility() {
+        assert_eq!(mobility(&Gamestate::starting_position()), (20, 20));
+
+        // After 1. e4 white has 30 moves, and black still 20
+        let game =
+            Gamestate::from_str("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1")
+                .unwrap();
+        assert_eq!(mobility(&game), (30, 20));
+    }
+
+    #[test]
+    fn test_positional_prefers_centralized_knight() {
+        let centre = Gamestate::from_str("4k3/8/8/8/4N3/8/8/4K3 w - - 0 1").unwrap();
+        let rim = Gamestate::from_str("4k3/8/8/8/N7/8/8/4K3 w - - 0 1").unwrap();
+        assert!(evaluate_positional(&centre.board) > evaluate_positional(&rim.board));
+        assert_eq!(evaluate_material(&centre.board), evaluate_material(&rim.board));
+
+        // The same holds for black, with the sign flipped
+        let centre = Gamestate::from_str("4k3/8/8/4n3/8/8/8/4K3 w - - 0 1").unwrap();
+        let rim = Gamestate::from_str("4k3/8/8/7n/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(evaluate_positional(&centre.board) < evaluate_positional(&rim.board));
+    }
+
+    #[test]
+    fn test_positional_is_color_symmetric() {
+        assert_eq!(evaluate_positional(&Gamestate::starting_position().board), 0);
+
+        let game = Gamestate::from_str(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        assert_eq!(
+            evaluate_positional(&game.mirror().board),
+            -evaluate_positional(&game.board)
+        );
+    }
+
+    #[test]
+    fn test_king_table_switches_in_endgame() {
+        // With only kings and pawns left, a central king is better than a castled one
+        let central = Gamestate::from_str("4k3/pppp4/8/8/4K3/8/PPPP4/8 w - - 0 1").unwrap();
+        let castled = Gamestate::from_str("4k3/pppp4/8/8/8/8/PPPP4/6K1 w - - 0 1").unwrap();
+        assert!(evaluate_positional(&central.board) > evaluate_positional(&castled.board));
+    }
+
+    #[test]
+    fn test_king_safety() {
+        // Every square around the cornered black king is covered by the queen or rook
+        let game = Gamestate::from_str("7k/5Q2/8/8/8/8/8/K5R1 b - - 0 1").unwrap();
+        assert_eq!(king_safety(&game, Color::Black), 3);
+        assert_eq!(king_safety(&game, Color::White), 0);
+
+        assert_eq!(king_safety(&Gamestate::starting_position(), Color::White), 0);
+    }
+}
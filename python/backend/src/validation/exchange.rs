@@ -0,0 +1,117 @@
+/*
+ * Author: Renier Barnard
+ * Static exchange evaluation (SEE)
+ *
+ * Plays out the sequence of captures on one square, each side always recapturing with
+ * its least valuable attacker and free to stop when continuing would lose material.
+ * Pins and checks are ignored, as is usual for SEE; pieces lined up behind an attacker
+ * join in once the attacker in front has captured.
+ */
+
+use super::attacks::attackers_by_type;
+use super::pieces::{Color, Piece};
+use crate::parsers::{fen_parser::Gamestate, notation::Move};
+
+/// Stands in for the king's value, so the king only recaptures last and capturing it
+/// outweighs anything else
+const KING_VALUE: i32 = 20_000;
+
+fn exchange_value(piece: char) -> i32 {
+    match Piece::from_char(piece) {
+        Some(Piece::King) => KING_VALUE,
+        Some(kind) => kind.value(),
+        None => 0,
+    }
+}
+
+/// Material won (positive) or lost by the capture `mv` once the exchange on its
+/// destination square has been played out, in centipawns for the side making it.
+/// A non-capture scores 0 unless the piece can then be taken.
+pub fn static_exchange(game: &Gamestate, mv: &Move) -> i32 {
+    let mut board = game.board;
+    let target = mv.to;
+    let mover = board[mv.from.0 as usize][mv.from.1 as usize];
+
+    // En passant takes a pawn that is not on the target square
+    let mut victim = board[target.0 as usize][target.1 as usize];
+    if mover.eq_ignore_ascii_case(&'p') && mv.from.1 != target.1 && victim == ' ' {
+        victim = board[mv.from.0 as usize][target.1 as usize];
+        board[mv.from.0 as usize][target.1 as usize] = ' ';
+    }
+
+    let mut gains = vec![exchange_value(victim)];
+    let mut on_target = mover;
+    board[target.0 as usize][target.1 as usize] = mover;
+    board[mv.from.0 as usize][mv.from.1 as usize] = ' ';
+
+    let mut side = Color::of(mover).map(Color::opposite);
+    while let Some(color) = side {
+        let attacker = attackers_by_type(target, &board, color)
+            .into_iter()
+            .min_by_key(|&(kind, _)| exchange_value(kind.to_char(color)));
+        let Some((kind, square)) = attacker else {
+            break;
+        };
+        let piece = kind.to_char(color);
+
+        // Each entry is what the side capturing at that step has gained overall if the
+        // exchange stops right after it
+        gains.push(exchange_value(on_target) - gains[gains.len() - 1]);
+        on_target = piece;
+        board[target.0 as usize][target.1 as usize] = piece;
+        board[square.0 as usize][square.1 as usize] = ' ';
+        side = Some(color.opposite());
+    }
+
+    // Work back from the end: each side only recaptures if that does not lose
+    for i in (1..gains.len()).rev() {
+        gains[i - 1] = -(-gains[i - 1]).max(gains[i]);
+    }
+    gains[0]
+}
+
+/// `static_exchange` for the capture from `from` to `to`, for callers holding squares
+/// rather than a `Move`
+pub fn see(game: &Gamestate, from: (u8, u8), to: (u8, u8)) -> i32 {
+    static_exchange(game, &Move::new(from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn see_fen(fen: &str, mv: Move) -> i32 {
+        static_exchange(&Gamestate::from_str(fen).unwrap(), &mv)
+    }
+
+    #[test]
+    fn test_undefended_and_defended_captures() {
+        // Rxe5 wins a free pawn
+        assert_eq!(see_fen("1k6/8/8/4p3/8/8/8/1K2R3 w - - 0 1", Move::new((7, 4), (3, 4))), 100);
+        // Pawn defended by a pawn: the rook is lost for a pawn
+        assert_eq!(see_fen("1k6/8/3p4/4p3/8/8/8/1K2R3 w - - 0 1", Move::new((7, 4), (3, 4))), -400);
+        // Pawn takes the defended queen: still up a lot
+        assert_eq!(see_fen("1k6/8/3p4/4q3/3P4/8/8/1K6 w - - 0 1", Move::new((4, 3), (3, 4))), 800);
+    }
+
+    #[test]
+    fn test_see_by_squares() {
+        // dxe5 wins the queen for a pawn; Qxd6 loses the queen for a pawn
+        let game = Gamestate::from_str("1k6/2p5/3p4/4q3/3P4/8/8/1K6 w - - 0 1").unwrap();
+        assert_eq!(see(&game, (4, 3), (3, 4)), 800);
+        let game = Gamestate::from_str("1k6/2p5/3p4/8/8/8/8/1K1Q4 w - - 0 1").unwrap();
+        assert_eq!(see(&game, (7, 3), (2, 3)), -800);
+    }
+
+    #[test]
+    fn test_x_ray_recapture() {
+        // The e1 rook backs up the e2 rook, so Rxe5 Rxe5 Rxe5 wins the pawn
+        let fen = "1k2r3/8/8/4p3/8/8/4R3/1K2R3 w - - 0 1";
+        assert_eq!(see_fen(fen, Move::new((6, 4), (3, 4))), 100);
+
+        // With a second black rook on e7 the exchange loses a rook for a pawn
+        let fen = "1k2r3/4r3/8/4p3/8/8/4R3/1K2R3 w - - 0 1";
+        assert_eq!(see_fen(fen, Move::new((6, 4), (3, 4))), -400);
+    }
+}
@@ -0,0 +1,422 @@
+/*
+ * Author: Renier Barnard
+ * Applying and taking back moves in place on a Gamestate
+ *
+ * apply_move assumes the move is legal; callers check it against get_legal_moves first,
+ * or use make_move, which checks the move and returns the new position.
+ */
+
+use super::possible_moves::{get_legal_moves, PROMOTION_PIECES};
+use crate::parsers::{fen_parser::Gamestate, notation::Move};
+
+/// What kind of move a `(from, to)` pair is in a given position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveKind {
+    /// A move to an empty square that is none of the special kinds below
+    Quiet,
+    Capture,
+    EnPassant,
+    Castle { kingside: bool },
+    /// A pawn reaching the back rank, with the lowercase piece it becomes
+    Promotion { piece: char, capture: bool },
+}
+
+/// Classifies the move from `from` to `to` before it is played. `promotion` picks the
+/// promotion piece, defaulting to a queen as in `apply_move`. The move is assumed to
+/// be legal.
+pub fn classify_move(
+    game: &Gamestate,
+    from: (u8, u8),
+    to: (u8, u8),
+    promotion: Option<char>,
+) -> MoveKind {
+    let piece = game.board[from.0 as usize][from.1 as usize];
+    let capture = game.board[to.0 as usize][to.1 as usize] != ' ';
+
+    let two_files = (from.1 as i8 - to.1 as i8).abs() == 2;
+    if piece.eq_ignore_ascii_case(&'k') && from.0 == to.0 && two_files {
+        return MoveKind::Castle {
+            kingside: to.1 > from.1,
+        };
+    }
+    if piece.eq_ignore_ascii_case(&'p') {
+        if to.0 == 0 || to.0 == 7 {
+            return MoveKind::Promotion {
+                piece: promotion.unwrap_or('q').to_ascii_lowercase(),
+                capture,
+            };
+        }
+        if from.1 != to.1 && !capture && game.enpassat == Some(to) {
+            return MoveKind::EnPassant;
+        }
+    }
+
+    if capture {
+        MoveKind::Capture
+    } else {
+        MoveKind::Quiet
+    }
+}
+
+/// The parts of a Gamestate that `apply_move` overwrites and `undo_move` needs back
+#[derive(Debug, Clone, Copy)]
+pub struct Undo {
+    /// The piece that moved, before any promotion
+    piece: char,
+    /// The captured piece, or `' '` if nothing was captured
+    captured: char,
+    /// Where the captured piece stood (differs from the destination for en passant)
+    captured_square: (u8, u8),
+    castling: (char, char, char, char),
+    enpassat: Option<(u8, u8)>,
+    halfmove: u8,
+    fullmove: u16,
+}
+
+/// Plays the move from `from` to `to` on a copy of `game` and returns the new position,
+/// with everything `apply_move` does applied. `promotion` picks the promotion piece
+/// (a queen if `None`) and is only accepted on a pawn reaching the back rank.
+///
+/// Fails if the move is not legal in `game`.
+pub fn make_move(
+    game: &Gamestate,
+    from: (u8, u8),
+    to: (u8, u8),
+    promotion: Option<char>,
+) -> Result<Gamestate, String> {
+    let mv = Move {
+        from,
+        to,
+        promotion: promotion.map(|piece| piece.to_ascii_lowercase()),
+    };
+    let legal = get_legal_moves(
+        &game.board,
+        game.enpassat.unwrap_or((0, 0)),
+        game.castling,
+        game.player,
+    )
+    .contains(&(from, to));
+    if !legal {
+        return Err(format!("Illegal move: {}", mv));
+    }
+
+    if let Some(piece) = mv.promotion {
+        let pawn = game.board[from.0 as usize][from.1 as usize].eq_ignore_ascii_case(&'p');
+        if !pawn || (to.0 != 0 && to.0 != 7) {
+            return Err(format!("Move {} does not promote", mv));
+        }
+        if !PROMOTION_PIECES.contains(&piece) {
+            return Err(format!("Invalid promotion piece: '{}'", piece));
+        }
+    }
+
+    let mut next = game.clone();
+    apply_move(&mut next, &mv);
+    Ok(next)
+}
+
+/// Applies `mv` to `game` in place, including castling rook moves, en passant
+/// captures, promotion (to a queen unless `mv.promotion` says otherwise), castling
+/// rights, the clocks and the side to move.
+///
+/// Returns the information needed to take the move back with `undo_move`.
+pub fn apply_move(game: &mut Gamestate, mv: &Move) -> Undo {
+    let (from, to) = (mv.from, mv.to);
+    let piece: char = game.board[from.0 as usize][from.1 as usize];
+    let target: char = game.board[to.0 as usize][to.1 as usize];
+
+    let mut undo = Undo {
+        piece,
+        captured: target,
+        captured_square: to,
+        castling: game.castling,
+        enpassat: game.enpassat,
+        halfmove: game.halfmove,
+        fullmove: game.fullmove,
+    };
+
+    game.board[to.0 as usize][to.1 as usize] = piece;
+    game.board[from.0 as usize][from.1 as usize] = ' ';
+
+    // Reset en passant by default (will be set again if pawn double-move)
+    game.enpassat = None;
+
+    if piece.eq_ignore_ascii_case(&'p') || target != ' ' {
+        game.halfmove = 0;
+    } else {
+        game.halfmove += 1;
+    }
+
+    // Handle pawn-specific moves
+    if piece.eq_ignore_ascii_case(&'p') {
+        if (from.0 as i8 - to.0 as i8).abs() == 2 {
+            // Double move - set en passant square
+            game.enpassat = Some(((from.0 + to.0) / 2, from.1));
+        } else if undo.enpassat == Some(to) && from.1 != to.1 && target == ' ' {
+            // En passant capture - remove the captured pawn
+            undo.captured_square = (from.0, to.1);
+            undo.captured = game.board[from.0 as usize][to.1 as usize];
+            game.board[from.0 as usize][to.1 as usize] = ' ';
+        }
+
+        // Pawn promotion - check if pawn reached opposite end
+        if to.0 == 0 || to.0 == 7 {
+            let promotion = mv.promotion.unwrap_or('q');
+            game.board[to.0 as usize][to.1 as usize] = if piece.is_uppercase() {
+                promotion.to_ascii_uppercase()
+            } else {
+                promotion.to_ascii_lowercase()
+            };
+        }
+    }
+
+    // Remove castling rights if rook is captured
+    if target == 'R' {
+        match to {
+            (7, 0) => game.castling.1 = '-', // White queenside rook captured
+            (7, 7) => game.castling.0 = '-', // White kingside rook captured
+            _ => (),
+        }
+    } else if target == 'r' {
+        match to {
+            (0, 0) => game.castling.3 = '-', // Black queenside rook captured
+            (0, 7) => game.castling.2 = '-', // Black kingside rook captured
+            _ => (),
+        }
+    }
+
+    // Handle castling moves and update castling rights
+    if piece == 'K' && from == (7, 4) {
+        game.castling.0 = '-';
+        game.castling.1 = '-';
+        match to {
+            (7, 6) => move_rook(game, 7, 7, 5), // Kingside castle
+            (7, 2) => move_rook(game, 7, 0, 3), // Queenside castle
+            _ => {}
+        }
+    } else if piece == 'k' && from == (0, 4) {
+        game.castling.2 = '-';
+        game.castling.3 = '-';
+        match to {
+            (0, 6) => move_rook(game, 0, 7, 5), // Kingside castle
+            (0, 2) => move_rook(game, 0, 0, 3), // Queenside castle
+            _ => {}
+        }
+    } else if piece == 'R' {
+        // White rook moved - update castling rights
+        match from {
+            (7, 0) => game.castling.1 = '-', // Queenside rook
+            (7, 7) => game.castling.0 = '-', // Kingside rook
+            _ => (),
+        };
+    } else if piece == 'r' {
+        // Black rook moved - update castling rights
+        match from {
+            (0, 0) => game.castling.3 = '-', // Queenside rook
+            (0, 7) => game.castling.2 = '-', // Kingside rook
+            _ => (),
+        };
+    };
+
+    // Switch player
+    game.player = if game.player == 'w' {
+        'b'
+    } else {
+        game.fullmove += 1;
+        'w'
+    };
+
+    undo
+}
+
+/// Takes back `mv`, which must be the last move applied to `game` with `apply_move`
+pub fn undo_move(game: &mut Gamestate, mv: &Move, undo: Undo) {
+    let (from, to) = (mv.from, mv.to);
+
+    game.board[from.0 as usize][from.1 as usize] = undo.piece;
+    game.board[to.0 as usize][to.1 as usize] = ' ';
+    game.board[undo.captured_square.0 as usize][undo.captured_square.1 as usize] = undo.captured;
+
+    // Put a castling rook back in its corner
+    if undo.piece.eq_ignore_ascii_case(&'k') && (from.1 as i8 - to.1 as i8).abs() == 2 {
+        if to.1 == 6 {
+            move_rook(game, from.0, 5, 7);
+        } else {
+            move_rook(game, from.0, 3, 0);
+        }
+    }
+
+    game.castling = undo.castling;
+    game.enpassat = undo.enpassat;
+    game.halfmove = undo.halfmove;
+    game.fullmove = undo.fullmove;
+    game.player = if game.player == 'w' { 'b' } else { 'w' };
+}
+
+fn move_rook(game: &mut Gamestate, rank: u8, from_file: u8, to_file: u8) {
+    game.board[rank as usize][to_file as usize] = game.board[rank as usize][from_file as usize];
+    game.board[rank as usize][from_file as usize] = ' ';
+}
+
+/// Debug check that every legal move in `game` can be applied and taken back
+/// without changing the board, castling rights, en passant square, clocks or turn.
+#[cfg(debug_assertions)]
+pub fn verify_make_unmake(game: &Gamestate) -> Result<(), String> {
+    use super::possible_moves::legal_moves;
+
+    let mut scratch = game.clone();
+    for mv in legal_moves(game) {
+        let undo = apply_move(&mut scratch, &mv);
+        undo_move(&mut scratch, &mv, undo);
+
+        let mismatch = if scratch.board != game.board {
+            Some("board")
+        } else if scratch.castling != game.castling {
+            Some("castling rights")
+        } else if scratch.enpassat != game.enpassat {
+            Some("en passant square")
+        } else if scratch.halfmove != game.halfmove || scratch.fullmove != game.fullmove {
+            Some("move clocks")
+        } else if scratch.player != game.player {
+            Some("side to move")
+        } else {
+            None
+        };
+
+        if let Some(field) = mismatch {
+            return Err(format!(
+                "{} differs after making and unmaking {} in {}",
+                field,
+                mv,
+                game.to_fen()
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn apply(fen: &str, mv: Move) -> Gamestate {
+        let mut game = Gamestate::from_str(fen).unwrap();
+        apply_move(&mut game, &mv);
+        game
+    }
+
+    #[test]
+    fn test_make_move_side_effects() {
+        let start = Gamestate::from_str("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let castled = make_move(&start, (7, 4), (7, 6), None).unwrap();
+        assert_eq!(castled.to_fen(), "r3k2r/8/8/8/8/8/8/R4RK1 b kq - 1 1");
+        // The original is untouched
+        assert_eq!(start.to_fen(), "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+
+        let game = Gamestate::from_str("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        let taken = make_move(&game, (3, 4), (2, 3), None).unwrap();
+        assert_eq!(taken.to_fen(), "4k3/8/3P4/8/8/8/8/4K3 b - - 0 2");
+
+        let game = Gamestate::from_str("1r2k3/P7/8/8/8/8/8/4K3 w - - 3 40").unwrap();
+        let promoted = make_move(&game, (1, 0), (0, 1), Some('R')).unwrap();
+        assert_eq!(promoted.to_fen(), "1R2k3/8/8/8/8/8/8/4K3 b - - 0 40");
+        assert_eq!(
+            make_move(&game, (1, 0), (0, 0), None).unwrap().to_fen(),
+            "Qr2k3/8/8/8/8/8/8/4K3 b - - 0 40"
+        );
+    }
+
+    #[test]
+    fn test_make_move_rejects_illegal_moves() {
+        let game = Gamestate::starting_position();
+        assert_eq!(
+            make_move(&game, (6, 4), (3, 4), None).unwrap_err(),
+            "Illegal move: e2-e5"
+        );
+        assert!(make_move(&game, (6, 4), (4, 4), Some('q')).is_err());
+
+        let game = Gamestate::from_str("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(make_move(&game, (1, 0), (0, 0), Some('k')).is_err());
+    }
+
+    #[test]
+    fn test_apply_castling_moves_rook() {
+        let game = apply("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", Move::new((7, 4), (7, 6)));
+        assert_eq!(game.to_fen(), "r3k2r/8/8/8/8/8/8/R4RK1 b kq - 1 1");
+    }
+
+    #[test]
+    fn test_rook_moves_clear_their_own_castling_right() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(apply(fen, Move::new((7, 0), (6, 0))).castling, ('K', '-', 'k', 'q'));
+        assert_eq!(apply(fen, Move::new((7, 7), (6, 7))).castling, ('-', 'Q', 'k', 'q'));
+
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1";
+        assert_eq!(apply(fen, Move::new((0, 0), (1, 0))).castling, ('K', 'Q', 'k', '-'));
+        assert_eq!(apply(fen, Move::new((0, 7), (1, 7))).castling, ('K', 'Q', '-', 'q'));
+    }
+
+    #[test]
+    fn test_rook_captures_clear_the_victims_castling_right() {
+        // The a1 rook takes the a8 rook: both queenside rights go
+        let game = apply("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", Move::new((7, 0), (0, 0)));
+        assert_eq!(game.castling, ('K', '-', 'k', '-'));
+    }
+
+    #[test]
+    fn test_apply_en_passant_removes_pawn() {
+        let game = apply("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2", Move::new((3, 4), (2, 3)));
+        assert_eq!(game.to_fen(), "4k3/8/3P4/8/8/8/8/4K3 b - - 0 2");
+    }
+
+    #[test]
+    fn test_apply_underpromotion() {
+        let game = apply("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", Move::with_promotion((1, 1), (0, 1), 'n'));
+        assert_eq!(game.to_fen(), "1N2k3/8/8/8/8/8/8/4K3 b - - 0 1");
+    }
+
+    #[test]
+    fn test_classify_move() {
+        let game = Gamestate::from_str("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        assert_eq!(classify_move(&game, (7, 0), (6, 0), None), MoveKind::Quiet);
+        assert_eq!(classify_move(&game, (7, 0), (0, 0), None), MoveKind::Capture);
+        assert_eq!(classify_move(&game, (3, 4), (2, 3), None), MoveKind::EnPassant);
+        assert_eq!(
+            classify_move(&game, (7, 4), (7, 6), None),
+            MoveKind::Castle { kingside: true }
+        );
+        assert_eq!(
+            classify_move(&game, (7, 4), (7, 2), None),
+            MoveKind::Castle { kingside: false }
+        );
+        assert_eq!(
+            classify_move(&game, (1, 1), (0, 1), Some('N')),
+            MoveKind::Promotion { piece: 'n', capture: false }
+        );
+        assert_eq!(
+            classify_move(&game, (1, 1), (0, 0), None),
+            MoveKind::Promotion { piece: 'q', capture: true }
+        );
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_verify_make_unmake() {
+        let positions = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            // Kiwipete: castling both ways, pins and captures everywhere
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            // En passant available
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            // Promotions with and without captures, for both sides
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            // Rook captures that remove castling rights
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ];
+        for fen in positions {
+            let game = Gamestate::from_str(fen).unwrap();
+            assert_eq!(verify_make_unmake(&game), Ok(()));
+        }
+    }
+}
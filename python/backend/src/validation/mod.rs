@@ -1,4 +1,12 @@
 /// Author: Renier Barnard
+pub mod annotated_moves;
+pub mod attacks;
 pub mod board_validation;
+pub mod draw;
+pub mod evaluation;
+pub mod exchange;
+pub mod make_move;
+pub mod perft;
 pub mod pieces;
 pub mod possible_moves;
+pub mod search;
@@ -0,0 +1,173 @@
+/*
+ * Author: Renier Barnard
+ * Perft: counting the leaf nodes of the legal move tree to a fixed depth
+ *
+ * The counts for well-known positions are published, which makes perft the standard
+ * way to check a move generator, and a steady workload for timing it.
+ */
+
+use super::make_move::{apply_move, undo_move};
+use super::possible_moves::legal_moves;
+use crate::parsers::fen_parser::Gamestate;
+use std::str::FromStr;
+
+/// Positions timed by `--bench`, as (name, FEN)
+pub const BENCH_POSITIONS: [(&str, &str); 5] = [
+    ("start", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+    ("kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"),
+    ("rook endgame", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"),
+    ("pawn race", "8/5k2/p7/1p6/6P1/7P/8/2K5 w - - 0 1"),
+    ("queen endgame", "6k1/5ppp/8/8/8/8/1q3PPP/3Q2K1 w - - 0 1"),
+];
+
+/// Counts the positions reached after exactly `depth` legal moves (half-moves) from `game`.
+/// Promotions count once per promotion piece.
+pub fn perft(game: &Gamestate, depth: u32) -> u64 {
+    let mut scratch = game.clone();
+    perft_in_place(&mut scratch, depth)
+}
+
+/// The perft counts of one perft-EPD line against the published ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpdResult {
+    pub fen: String,
+    /// `(depth, expected, actual)` for each depth the line lists
+    pub depths: Vec<(u32, u64, u64)>,
+}
+
+impl EpdResult {
+    pub fn passed(&self) -> bool {
+        self.depths
+            .iter()
+            .all(|&(_, expected, actual)| expected == actual)
+    }
+}
+
+/// Runs one line of a perft-EPD suite, e.g. `<FEN> ;D1 20 ;D2 400 ;D3 8902`. The FEN
+/// may leave out the move clocks, as many suites do.
+pub fn perft_epd_line(line: &str) -> Result<EpdResult, String> {
+    let mut fields = line.split(';');
+    let fen = fields.next().unwrap_or_default().trim();
+    let game = Gamestate::from_str(fen)?;
+
+    let depths = fields
+        .map(|field| {
+            let field = field.trim();
+            let (depth, count) = field
+                .strip_prefix('D')
+                .and_then(|rest| rest.split_once(' '))
+                .ok_or_else(|| format!("Invalid perft field: '{}'", field))?;
+            let depth: u32 = depth.parse().map_err(|_| format!("Invalid depth: '{}'", depth))?;
+            let expected: u64 = count
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid node count: '{}'", count))?;
+            Ok((depth, expected, perft(&game, depth)))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(EpdResult {
+        fen: fen.to_string(),
+        depths,
+    })
+}
+
+fn perft_in_place(game: &mut Gamestate, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = legal_moves(game);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for mv in moves {
+        let undo = apply_move(game, &mv);
+        nodes += perft_in_place(game, depth - 1);
+        undo_move(game, &mv, undo);
+    }
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reference positions from the Chess Programming Wiki "Perft Results" page, with
+    /// their published node counts for depths 1 to 4
+    const PERFT_POSITIONS: [(&str, [u64; 4]); 5] = [
+        // Position 2, "Kiwipete": castling both ways, pins, en passant and promotions
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            [48, 2_039, 97_862, 4_085_603],
+        ),
+        // Position 3: sparse rook endgame, en passant captures that expose the king
+        // along the rank
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", [14, 191, 2_812, 43_238]),
+        // Position 4: white in check from the start, promotions with capture, and
+        // black castling rights only
+        (
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            [6, 264, 9_467, 422_333],
+        ),
+        // Position 5: a promotion on d8 and checks by discovery
+        (
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            [44, 1_486, 62_379, 2_103_487],
+        ),
+        // Position 6: a symmetrical middlegame with pins on both sides
+        (
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            [46, 2_079, 89_890, 3_894_594],
+        ),
+    ];
+
+    fn check_perft(max_depth: u32) {
+        for (fen, counts) in PERFT_POSITIONS {
+            let game = Gamestate::from_str(fen).unwrap();
+            for depth in 1..=max_depth {
+                let expected = counts[depth as usize - 1];
+                assert_eq!(perft(&game, depth), expected, "depth {} of {}", depth, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn test_perft_reference_positions() {
+        check_perft(3);
+    }
+
+    /// Millions of nodes per position; run with `cargo test --release -- --ignored`
+    #[test]
+    #[ignore]
+    fn test_perft_reference_positions_depth_4() {
+        check_perft(4);
+    }
+
+    #[test]
+    fn test_perft_epd() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 400";
+        let result = perft_epd_line(start).unwrap();
+        assert!(result.passed());
+        assert_eq!(result.depths, vec![(1, 20, 20), (2, 400, 400)]);
+
+        assert!(!perft_epd_line("8/8/8/8/8/8/8/K6k w - - ;D1 4").unwrap().passed());
+        assert!(perft_epd_line("8/8/8/8/8/8/8/K6k w - - ;X1 4").is_err());
+
+        let fixture = include_str!("../../fixtures/perft.epd");
+        for line in fixture.lines().filter(|line| !line.trim().is_empty()) {
+            assert!(perft_epd_line(line).unwrap().passed(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_perft_start_position() {
+        let game = Gamestate::starting_position();
+        assert_eq!(perft(&game, 0), 1);
+        assert_eq!(perft(&game, 1), 20);
+        assert_eq!(perft(&game, 2), 400);
+        assert_eq!(perft(&game, 3), 8_902);
+    }
+}
@@ -17,7 +17,6 @@ Performance Fix: Removed unnecessary parallelization
 /// # Returns
 ///
 /// A vector of vectors where each inner vector represents positions the bishop can move to in one direction.
-
 pub fn get_possible_moves(from: (u8, u8), board: &[[char; 8]; 8]) -> Vec<Vec<(u8, u8)>> {
     // PERFORMANCE FIX: Removed par_iter() - only 4 directions, overhead > benefit
     vec![
@@ -7,3 +7,165 @@ pub mod knight;
 pub mod pawn;
 pub mod queen;
 pub mod rook;
+
+/// The side a piece belongs to. On the board, white pieces are uppercase and black pieces lowercase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    White,
+    Black,
+}
+
+impl Color {
+    /// Converts a FEN side-to-move character (`'w'`/`'b'`) to a color
+    pub fn from_player(player: char) -> Self {
+        if player == 'w' {
+            Color::White
+        } else {
+            Color::Black
+        }
+    }
+
+    /// Returns the color of a board character, or `None` for an empty square
+    pub fn of(piece: char) -> Option<Self> {
+        match piece {
+            ' ' => None,
+            c if c.is_uppercase() => Some(Color::White),
+            _ => Some(Color::Black),
+        }
+    }
+
+    /// Converts the color back to its FEN side-to-move character
+    pub fn to_player(self) -> char {
+        match self {
+            Color::White => 'w',
+            Color::Black => 'b',
+        }
+    }
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+
+/// A piece type, independent of its color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Piece {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+impl Piece {
+    pub const ALL: [Piece; 6] = [
+        Piece::Pawn,
+        Piece::Knight,
+        Piece::Bishop,
+        Piece::Rook,
+        Piece::Queen,
+        Piece::King,
+    ];
+
+    /// Parses a board character of either case (e.g. `'N'` or `'n'`)
+    pub fn from_char(c: char) -> Option<Self> {
+        match c.to_ascii_lowercase() {
+            'p' => Some(Piece::Pawn),
+            'n' => Some(Piece::Knight),
+            'b' => Some(Piece::Bishop),
+            'r' => Some(Piece::Rook),
+            'q' => Some(Piece::Queen),
+            'k' => Some(Piece::King),
+            _ => None,
+        }
+    }
+
+    /// Returns the board character for this piece in the given color
+    pub fn to_char(self, color: Color) -> char {
+        let c = match self {
+            Piece::Pawn => 'p',
+            Piece::Knight => 'n',
+            Piece::Bishop => 'b',
+            Piece::Rook => 'r',
+            Piece::Queen => 'q',
+            Piece::King => 'k',
+        };
+        match color {
+            Color::White => c.to_ascii_uppercase(),
+            Color::Black => c,
+        }
+    }
+
+    /// Material value in centipawns. The king has no material value.
+    pub fn value(self) -> i32 {
+        match self {
+            Piece::Pawn => 100,
+            Piece::Knight => 320,
+            Piece::Bishop => 330,
+            Piece::Rook => 500,
+            Piece::Queen => 900,
+            Piece::King => 0,
+        }
+    }
+}
+
+/// Iterates over the occupied squares of a board, rank 8 first, yielding each square
+/// and the piece character on it
+pub fn occupied_squares(board: &[[char; 8]; 8]) -> impl Iterator<Item = ((u8, u8), char)> + '_ {
+    board.iter().enumerate().flat_map(|(rank, row)| {
+        row.iter()
+            .enumerate()
+            .filter(|&(_, &piece)| piece != ' ')
+            .map(move |(file, &piece)| ((rank as u8, file as u8), piece))
+    })
+}
+
+/// The bit for `square` in a 64-bit occupancy set. Squares are numbered `row * 8 + col`
+/// in board order, so a8 is bit 0, h8 bit 7, a1 bit 56 and h1 bit 63.
+pub fn square_bit(square: (u8, u8)) -> u64 {
+    1 << (square.0 as u32 * 8 + square.1 as u32)
+}
+
+/// Bitset of the occupied squares of a board, numbered as in `square_bit`
+pub fn occupancy(board: &[[char; 8]; 8]) -> u64 {
+    occupied_squares(board).fold(0, |bits, (square, _)| bits | square_bit(square))
+}
+
+/// Returns the square of `color`'s king, or `None` if it has no king on the board
+pub fn find_king(board: &[[char; 8]; 8], color: Color) -> Option<(u8, u8)> {
+    let king = Piece::King.to_char(color);
+    occupied_squares(board)
+        .find(|&(_, piece)| piece == king)
+        .map(|(square, _)| square)
+}
+
+/// Counts how many pieces of the given type and color are on the board
+pub fn piece_count(board: &[[char; 8]; 8], piece: Piece, color: Color) -> u8 {
+    let target = piece.to_char(color);
+    board
+        .iter()
+        .flatten()
+        .filter(|&&square| square == target)
+        .count() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_king() {
+        let mut board = [[' '; 8]; 8];
+        board[7][4] = 'K'; // e1
+        board[0][4] = 'k'; // e8
+        assert_eq!(find_king(&board, Color::White), Some((7, 4)));
+        assert_eq!(find_king(&board, Color::Black), Some((0, 4)));
+
+        board[0][4] = ' ';
+        assert_eq!(find_king(&board, Color::Black), None);
+    }
+}
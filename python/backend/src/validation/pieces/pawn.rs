@@ -38,8 +38,11 @@ pub fn get_possible_moves(
             return vec![positions_attack, positions_regular]; // Can't move off board
         }
         
-        // Double move from starting position
-        if x == WHITE_PAWN_START_RANK && board[(x - 2) as usize][y as usize] == ' ' {
+        // Double move from starting position (both squares ahead must be empty)
+        if x == WHITE_PAWN_START_RANK
+            && board[(x - 1) as usize][y as usize] == ' '
+            && board[(x - 2) as usize][y as usize] == ' '
+        {
             positions_regular.push((x - 2, y));
         }
         x - 1
@@ -49,13 +52,20 @@ pub fn get_possible_moves(
             return vec![positions_attack, positions_regular]; // Can't move off board
         }
         
-        // Double move from starting position
-        if x == BLACK_PAWN_START_RANK && board[(x + 2) as usize][y as usize] == ' ' {
+        // Double move from starting position (both squares ahead must be empty)
+        if x == BLACK_PAWN_START_RANK
+            && board[(x + 1) as usize][y as usize] == ' '
+            && board[(x + 2) as usize][y as usize] == ' '
+        {
             positions_regular.push((x + 2, y));
         }
         x + 1
     };
 
+    // An en passant target is always on rank 6 for white and rank 3 for black, which
+    // also keeps the (0, 0) "no en passant" value from matching a8
+    let en_passant_row = if from_piece_is_uppercase { 2 } else { 5 };
+
     // Regular forward move
     if board[new_x as usize][y as usize] == ' ' {
         positions_regular.push((new_x, y));
@@ -71,7 +81,7 @@ pub fn get_possible_moves(
         }
         
         // En passant left
-        if capture_pos == enpassat {
+        if capture_pos == enpassat && target == ' ' && new_x == en_passant_row {
             positions_attack.push(capture_pos);
         }
     }
@@ -86,7 +96,7 @@ pub fn get_possible_moves(
         }
         
         // En passant right
-        if capture_pos == enpassat {
+        if capture_pos == enpassat && target == ' ' && new_x == en_passant_row {
             positions_attack.push(capture_pos);
         }
     }
@@ -111,6 +121,28 @@ mod tests {
         assert!(all_moves.contains(&(4, 4)));
     }
 
+    #[test]
+    fn test_pawn_double_move_blocked() {
+        let mut board = [[' '; 8]; 8];
+        board[6][0] = 'P'; // White pawn at a2
+        board[5][0] = 'p'; // Black pawn at a3
+        board[1][7] = 'p'; // Black pawn at h7
+        board[2][7] = 'N'; // White knight at h6
+
+        let white: Vec<(u8, u8)> = get_possible_moves((6, 0), &board, (0, 0))
+            .into_iter()
+            .flatten()
+            .collect();
+        let black: Vec<(u8, u8)> = get_possible_moves((1, 7), &board, (0, 0))
+            .into_iter()
+            .flatten()
+            .collect();
+
+        // Neither pawn may jump over the piece in front of it
+        assert!(white.is_empty());
+        assert!(black.is_empty());
+    }
+
     #[test]
     fn test_pawn_capture() {
         let mut board = [[' '; 8]; 8];
@@ -1,4 +1,4 @@
-/**
+/*
  * Author: Renier Barnard
  * Performance Fix: Removed rayon - sequential is faster for only 8 directions
 */
@@ -1,12 +1,119 @@
-use super::pieces::{bishop, king, knight, pawn, queen, rook};
+use super::attacks::{attacked_squares, AttackTable};
+use super::make_move::{apply_move, classify_move, MoveKind};
+use super::pieces::{
+    bishop, find_king, king, knight, occupied_squares, pawn, queen, rook, Color, Piece,
+};
+use crate::parsers::{
+    fen_parser::Gamestate,
+    notation::{index_to_chess_notation, Move},
+};
 use rayon::prelude::*;
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
 
-/// Determines the current game status after a move
+/// Pieces a pawn may promote to, strongest first
+pub const PROMOTION_PIECES: [char; 4] = ['q', 'r', 'b', 'n'];
+
+/// A move as a `(from, to)` pair of board coordinates
+pub type CoordMove = ((u8, u8), (u8, u8));
+
+/// A `(from, to)` move with its promotion piece, if any
+pub type CoordPromotionMove = ((u8, u8), (u8, u8), Option<char>);
+
+/// A scanned piece: its rank, file, character and per-direction move lists
+type ScannedPiece = (u8, u8, char, Vec<Vec<(u8, u8)>>);
+
+/// An enemy attack direction paired with the square of the attacking piece
+type AttackPath = (Vec<(u8, u8)>, (u8, u8));
+
+/// Determines the current game status after a move. Serialized in snake_case, e.g.
+/// `"checkmate"` or `"draw_by_fifty_move_rule"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum GameStatus {
+    #[default]
     Ongoing,
     Check,
     Checkmate,
     Stalemate,
+    /// Drawn by the 50-move rule. `get_game_status` only looks at the board and never
+    /// returns this, `DrawByInsufficientMaterial` or `DrawByRepetition`; the game
+    /// handler does.
+    DrawByFiftyMoveRule,
+    /// Neither side has the material left to mate
+    DrawByInsufficientMaterial,
+    /// The same position has occurred five times. A third occurrence only makes the
+    /// draw claimable; the game goes on until a player claims it.
+    DrawByRepetition,
+}
+
+/// Why a side may not castle in a position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastleDenial {
+    /// The castling right is gone, or the king or rook is not on its starting square
+    NoRight,
+    /// A piece stands between the king and the rook
+    PathBlocked,
+    InCheck,
+    /// The square the king crosses (f or d file) is attacked
+    PassesThroughCheck,
+    /// The square the king lands on (g or c file) is attacked
+    EndsInCheck,
+}
+
+/// Checks whether `color` may castle on the given side right now, and if not, why.
+/// Whose turn it is does not matter.
+pub fn castling_legality(
+    game: &Gamestate,
+    color: Color,
+    kingside: bool,
+) -> Result<(), CastleDenial> {
+    let rank = if color == Color::White { 7 } else { 0 };
+
+    // Attacks are taken with the king lifted off, as elsewhere in move generation
+    let mut board = game.board;
+    board[rank][4] = ' ';
+    let enemy_attacks = AttackTable::new(&board, color.opposite());
+
+    check_castling(&game.board, game.castling, color, kingside, &enemy_attacks)
+}
+
+/// `castling_legality` against an already built table of the enemy's attacks
+fn check_castling(
+    board: &[[char; 8]; 8],
+    castling: (char, char, char, char),
+    color: Color,
+    kingside: bool,
+    enemy_attacks: &AttackTable,
+) -> Result<(), CastleDenial> {
+    let rank = if color == Color::White { 7 } else { 0 };
+    let right = match (color, kingside) {
+        (Color::White, true) => castling.0 == 'K',
+        (Color::White, false) => castling.1 == 'Q',
+        (Color::Black, true) => castling.2 == 'k',
+        (Color::Black, false) => castling.3 == 'q',
+    };
+    let rook_file = if kingside { 7 } else { 0 };
+    if !right
+        || board[rank as usize][4] != Piece::King.to_char(color)
+        || board[rank as usize][rook_file] != Piece::Rook.to_char(color)
+    {
+        return Err(CastleDenial::NoRight);
+    }
+    if !king::castle_path_clear(board, castling, color, kingside) {
+        return Err(CastleDenial::PathBlocked);
+    }
+
+    let (crossed, landing) = if kingside { (5, 6) } else { (3, 2) };
+    if enemy_attacks.is_attacked((rank, 4)) {
+        Err(CastleDenial::InCheck)
+    } else if enemy_attacks.is_attacked((rank, crossed)) {
+        Err(CastleDenial::PassesThroughCheck)
+    } else if enemy_attacks.is_attacked((rank, landing)) {
+        Err(CastleDenial::EndsInCheck)
+    } else {
+        Ok(())
+    }
 }
 
 pub fn get_legal_moves(
@@ -14,10 +121,10 @@ pub fn get_legal_moves(
     enpassat: (u8, u8),
     castling: (char, char, char, char),
     player: char,
-) -> Vec<((u8, u8), (u8, u8))> {
+) -> Vec<CoordMove> {
     // OPTIMIZATION: Parallel board scan (64 squares) - this is where Rayon helps
     // Collect everything in one parallel pass, then process sequentially
-    let board_data: Vec<(u8, u8, char, Vec<Vec<(u8, u8)>>)> = (0u8..8)
+    let board_data: Vec<ScannedPiece> = (0u8..8)
         .into_par_iter()
         .flat_map(|rank| {
             (0u8..8).into_par_iter().filter_map(move |file| {
@@ -43,18 +150,13 @@ pub fn get_legal_moves(
         .collect();
 
     // Sequential processing (better for complex logic with branches)
-    let mut king_position = (10u8, 10u8);
-    let mut attacking_paths: Vec<(Vec<(u8, u8)>, (u8, u8))> = Vec::new();
-    let mut positions: Vec<((u8, u8), (u8, u8))> = Vec::with_capacity(64);
+    let king_position = find_king(board, Color::from_player(player));
+    let mut attacking_paths: Vec<AttackPath> = Vec::new();
+    let mut positions: Vec<CoordMove> = Vec::with_capacity(64);
 
     for (rank, file, piece, move_directions) in board_data {
         let from = (rank, file);
 
-        // Find king
-        if piece.to_ascii_lowercase() == 'k' && piece.is_uppercase() == (player == 'w') {
-            king_position = from;
-        }
-
         if piece.is_uppercase() != (player == 'w') {
             // Enemy piece: store all its attack directions for later king checking
             for direction in move_directions {
@@ -77,7 +179,7 @@ pub fn get_legal_moves(
     let mut actual_attackers: Vec<(u8, u8)> = Vec::new();
     
     for (path, attacker) in attacking_paths {
-        if path.contains(&king_position) {
+        if king_position.is_some_and(|king| path.contains(&king)) {
             actual_attacks.push(path);
             actual_attackers.push(attacker);
         }
@@ -86,33 +188,30 @@ pub fn get_legal_moves(
     let in_check = !actual_attackers.is_empty();
     let double_check = actual_attackers.len() > 1;
 
+    // Squares the enemy attacks, worked out once with our king lifted off the board so
+    // that a slider checking the king also covers the square behind it
+    let mut board_without_king = *board;
+    if let Some((rank, file)) = king_position {
+        board_without_king[rank as usize][file as usize] = ' ';
+    }
+    let enemy_attacks = AttackTable::new(&board_without_king, Color::from_player(player).opposite());
+
     // Filter moves for legality (sequential - complex branching logic)
     positions
         .into_iter()
         .filter(|&(from, to)| {
-            if from == king_position {
+            if Some(from) == king_position {
                 // Special handling for castling moves
                 let is_castling = from.0 == to.0 && (from.1 as i8 - to.1 as i8).abs() == 2;
 
                 if is_castling {
-                    if in_check {
-                        return false;
-                    }
-
-                    let is_kingside = to.1 > from.1;
-                    let intermediate_y = if is_kingside { from.1 + 1 } else { from.1 - 1 };
-
-                    if is_square_attacked((from.0, intermediate_y), board, enpassat, player) {
-                        return false;
-                    }
+                    let color = Color::from_player(player);
+                    return check_castling(board, castling, color, to.1 > from.1, &enemy_attacks)
+                        .is_ok();
                 }
 
                 // King cannot move into check
-                let mut temp_board = *board;
-                temp_board[to.0 as usize][to.1 as usize] =
-                    temp_board[from.0 as usize][from.1 as usize];
-                temp_board[from.0 as usize][from.1 as usize] = ' ';
-                return !is_square_attacked(to, &temp_board, enpassat, player);
+                return !enemy_attacks.is_attacked(to);
             }
 
             if in_check {
@@ -121,17 +220,291 @@ pub fn get_legal_moves(
                 }
 
                 let is_in_attack_path = actual_attacks.iter().any(|path| path.contains(&to));
-                if let Some(&attacker) = actual_attackers.first() {
-                    return to == attacker || is_in_attack_path;
+                // An en passant capture removes a checking pawn without landing on it
+                let piece = board[from.0 as usize][from.1 as usize];
+                let captures_en_passant = piece.eq_ignore_ascii_case(&'p')
+                    && to == enpassat
+                    && from.1 != to.1
+                    && board[to.0 as usize][to.1 as usize] == ' ';
+                let resolves_check = match actual_attackers.first() {
+                    Some(&attacker) => {
+                        to == attacker
+                            || is_in_attack_path
+                            || (captures_en_passant && (from.0, to.1) == attacker)
+                    }
+                    None => false,
+                };
+                if !resolves_check {
+                    return false;
                 }
-                return false;
             }
 
-            true
+            // Pinned pieces (and en passant captures) must not expose the king
+            !move_exposes_king(board, from, to, enpassat)
+        })
+        .collect()
+}
+
+/// Returns the legal moves for the side to move as `Move`s.
+///
+/// Unlike `get_legal_moves`, a pawn reaching the back rank yields one move per
+/// promotion piece (Q, R, B, N), so underpromotions are counted separately.
+pub fn legal_moves(game: &Gamestate) -> Vec<Move> {
+    let mut moves = Vec::new();
+    for (from, to) in get_legal_moves(
+        &game.board,
+        game.enpassat.unwrap_or((0, 0)),
+        game.castling,
+        game.player,
+    ) {
+        let piece = game.board[from.0 as usize][from.1 as usize];
+        if piece.eq_ignore_ascii_case(&'p') && (to.0 == 0 || to.0 == 7) {
+            for promotion in PROMOTION_PIECES {
+                moves.push(Move::with_promotion(from, to, promotion));
+            }
+        } else {
+            moves.push(Move::new(from, to));
+        }
+    }
+    moves
+}
+
+/// Returns every legal promotion for the pawn on `from`, one `Move` per promotion
+/// piece for each reachable back-rank square (pushes and captures alike).
+pub fn legal_promotions(game: &Gamestate, from: (u8, u8)) -> Vec<Move> {
+    legal_moves(game)
+        .into_iter()
+        .filter(|m| m.from == from && m.promotion.is_some())
+        .collect()
+}
+
+/// Returns the legal moves of the side to move's pieces of type `piece`, e.g. all its
+/// knight moves. Pawn promotions come once per promotion piece, as in `legal_moves`.
+pub fn legal_moves_for_piece(game: &Gamestate, piece: Piece) -> Vec<Move> {
+    let target = piece.to_char(Color::from_player(game.player));
+    legal_moves(game)
+        .into_iter()
+        .filter(|mv| game.board[mv.from.0 as usize][mv.from.1 as usize] == target)
+        .collect()
+}
+
+/// Returns the legal captures for the side to move, en passant and capturing
+/// promotions included. An en passant capture lands on an empty square, so it is told
+/// apart by `game.enpassat`; one that would expose the king is not legal and is left out.
+pub fn capture_moves(game: &Gamestate) -> Vec<Move> {
+    legal_moves(game)
+        .into_iter()
+        .filter(|mv| {
+            matches!(
+                classify_move(game, mv.from, mv.to, mv.promotion),
+                MoveKind::Capture | MoveKind::EnPassant | MoveKind::Promotion { capture: true, .. }
+            )
         })
         .collect()
 }
 
+/// True when the side to move is not in check and has no capture available
+pub fn is_quiet(game: &Gamestate) -> bool {
+    !is_in_check(
+        &game.board,
+        game.enpassat.unwrap_or((0, 0)),
+        game.castling,
+        game.player,
+    ) && capture_moves(game).is_empty()
+}
+
+/// Returns the squares the piece on `square` can legally move to. Pins and check are
+/// respected, since this filters the full legal move list.
+pub fn legal_moves_from(game: &Gamestate, square: (u8, u8)) -> Vec<(u8, u8)> {
+    get_legal_moves(
+        &game.board,
+        game.enpassat.unwrap_or((0, 0)),
+        game.castling,
+        game.player,
+    )
+    .into_iter()
+    .filter(|&(from, _)| from == square)
+    .map(|(_, to)| to)
+    .collect()
+}
+
+/// Returns the squares the piece on `square` can legally move to that give check, for
+/// highlighting checks in a trainer. Promotions are tried as a queen, as `apply_move`
+/// defaults to.
+pub fn checking_destinations_from(game: &Gamestate, square: (u8, u8)) -> Vec<(u8, u8)> {
+    let opponent = Color::from_player(game.player).opposite().to_player();
+    legal_moves_from(game, square)
+        .into_iter()
+        .filter(|&to| {
+            let mut after = game.clone();
+            apply_move(&mut after, &Move::new(square, to));
+            is_in_check(&after.board, (0, 0), after.castling, opponent)
+        })
+        .collect()
+}
+
+/// Returns the legal moves for the side to move grouped by source square: each square
+/// with a movable piece maps to the squares it can reach, sorted in board order so the
+/// lists don't depend on the order the parallel generator found them in
+pub fn legal_moves_map(game: &Gamestate) -> HashMap<(u8, u8), Vec<(u8, u8)>> {
+    let mut map: HashMap<(u8, u8), Vec<(u8, u8)>> = HashMap::new();
+    for (from, to) in get_legal_moves(
+        &game.board,
+        game.enpassat.unwrap_or((0, 0)),
+        game.castling,
+        game.player,
+    ) {
+        map.entry(from).or_default().push(to);
+    }
+    for destinations in map.values_mut() {
+        destinations.sort();
+    }
+    map
+}
+
+/// Returns the squares of the side-to-move's pieces that can legally move to `square`
+pub fn legal_moves_to(game: &Gamestate, square: (u8, u8)) -> Vec<(u8, u8)> {
+    get_legal_moves(
+        &game.board,
+        game.enpassat.unwrap_or((0, 0)),
+        game.castling,
+        game.player,
+    )
+    .into_iter()
+    .filter(|&(_, to)| to == square)
+    .map(|(from, _)| from)
+    .collect()
+}
+
+/// Returns true when moving the piece on `from` to `to` would leave the mover's king in
+/// check. The move is played on a copy of the game, so en passant captures and castling
+/// rook moves are taken into account. Whether the piece can make the move at all is not
+/// checked.
+pub fn leaves_king_in_check(game: &Gamestate, from: (u8, u8), to: (u8, u8)) -> bool {
+    let player = game.player;
+    let mut after = game.clone();
+    apply_move(&mut after, &Move::new(from, to));
+    is_in_check(&after.board, (0, 0), after.castling, player)
+}
+
+/// Returns true if the side to move has at least one legal move, stopping at the
+/// first one found instead of building the whole legal move list.
+pub fn has_any_legal_move(game: &Gamestate) -> bool {
+    // Castling is skipped: whenever it is legal, so is the king's single step towards
+    // the rook
+    let no_castling = ('-', '-', '-', '-');
+    game.pieces_of(Color::from_player(game.player)).any(|(from, _)| {
+        pseudo_moves(game, from, no_castling)
+            .into_iter()
+            .any(|to| !leaves_king_in_check(game, from, to))
+    })
+}
+
+/// Squares the piece on `from` could move to if its own king's safety were ignored
+fn pseudo_moves(
+    game: &Gamestate,
+    from: (u8, u8),
+    castling: (char, char, char, char),
+) -> Vec<(u8, u8)> {
+    let board = &game.board;
+    let directions = match Piece::from_char(board[from.0 as usize][from.1 as usize]) {
+        Some(Piece::Pawn) => {
+            pawn::get_possible_moves(from, board, game.enpassat.unwrap_or((0, 0)))
+        }
+        Some(Piece::Knight) => knight::get_possible_moves(from, board),
+        Some(Piece::Bishop) => bishop::get_possible_moves(from, board),
+        Some(Piece::Rook) => rook::get_possible_moves(from, board),
+        Some(Piece::Queen) => queen::get_possible_moves(from, board),
+        Some(Piece::King) => king::get_possible_moves(from, board, castling),
+        None => Vec::new(),
+    };
+    directions.into_iter().flatten().collect()
+}
+
+/// Explains why moving from `from` to `to` (promoting to `promotion`) is not legal for
+/// the side to move, e.g. "the knight on g1 cannot move to g4". Returns `None` if the
+/// move is legal.
+pub fn explain_illegal(
+    game: &Gamestate,
+    from: (u8, u8),
+    to: (u8, u8),
+    promotion: Option<char>,
+) -> Option<String> {
+    let (Some(from_name), Some(to_name)) =
+        (index_to_chess_notation(from), index_to_chess_notation(to))
+    else {
+        return Some("square is off the board".to_string());
+    };
+    let piece = game.board[from.0 as usize][from.1 as usize];
+    let (Some(kind), Some(color)) = (Piece::from_char(piece), Color::of(piece)) else {
+        return Some(format!("there is no piece on {}", from_name));
+    };
+    if color != Color::from_player(game.player) {
+        let side = |color: Color| format!("{:?}", color).to_lowercase();
+        return Some(format!(
+            "not your turn: the piece on {} is {} and {} is to move",
+            from_name,
+            side(color),
+            side(color.opposite())
+        ));
+    }
+    let name = format!("{:?}", kind).to_lowercase();
+
+    let castles = kind == Piece::King && from.0 == to.0 && from.1.abs_diff(to.1) == 2;
+    if castles {
+        let denial = castling_legality(game, color, to.1 > from.1).err()?;
+        return Some(
+            match denial {
+                CastleDenial::NoRight => "castling rights are gone",
+                CastleDenial::PathBlocked => "castling path is blocked",
+                CastleDenial::InCheck => "cannot castle out of check",
+                CastleDenial::PassesThroughCheck => "king would pass through check",
+                CastleDenial::EndsInCheck => "king would end up in check",
+            }
+            .to_string(),
+        );
+    }
+
+    if !pseudo_moves(game, from, ('-', '-', '-', '-')).contains(&to) {
+        return Some(format!("the {} on {} cannot move to {}", name, from_name, to_name));
+    }
+    if leaves_king_in_check(game, from, to) {
+        return Some("the move would leave the king in check".to_string());
+    }
+    if let Some(piece) = promotion {
+        if kind != Piece::Pawn || (to.0 != 0 && to.0 != 7) {
+            return Some(format!("{}-{} is not a promotion", from_name, to_name));
+        }
+        if !PROMOTION_PIECES.contains(&piece.to_ascii_lowercase()) {
+            return Some(format!("cannot promote to '{}'", piece));
+        }
+    }
+    None
+}
+
+/// Checks whether moving the piece on `from` to `to` would leave its own king in check
+fn move_exposes_king(
+    board: &[[char; 8]; 8],
+    from: (u8, u8),
+    to: (u8, u8),
+    enpassat: (u8, u8),
+) -> bool {
+    let piece = board[from.0 as usize][from.1 as usize];
+    let is_en_passant = piece.eq_ignore_ascii_case(&'p')
+        && to == enpassat
+        && from.1 != to.1
+        && board[to.0 as usize][to.1 as usize] == ' ';
+    if is_en_passant {
+        return en_passant_exposes_king(board, from, to);
+    }
+
+    let player = if piece.is_uppercase() { 'w' } else { 'b' };
+    let mut temp_board = *board;
+    temp_board[to.0 as usize][to.1 as usize] = piece;
+    temp_board[from.0 as usize][from.1 as usize] = ' ';
+    is_in_check(&temp_board, enpassat, ('-', '-', '-', '-'), player)
+}
+
 /// Checks if the current player is in check
 pub fn is_in_check(
     board: &[[char; 8]; 8],
@@ -139,26 +512,68 @@ pub fn is_in_check(
     _castling: (char, char, char, char),
     player: char,
 ) -> bool {
-    // Find the king (sequential - early exit optimization)
-    let mut king_pos = None;
-    for rank in 0u8..8 {
-        for file in 0u8..8 {
-            let piece = board[rank as usize][file as usize];
-            if piece.to_ascii_lowercase() == 'k' && piece.is_uppercase() == (player == 'w') {
-                king_pos = Some((rank, file));
-                break;
-            }
-        }
-        if king_pos.is_some() {
-            break;
-        }
+    match find_king(board, Color::from_player(player)) {
+        Some(king_position) => is_square_attacked(king_position, board, enpassat, player),
+        None => false,
     }
+}
 
-    if let Some(king_position) = king_pos {
-        is_square_attacked(king_position, board, enpassat, player)
-    } else {
-        false
-    }
+/// Returns the squares of the enemy pieces giving check to the side to move: none,
+/// one, or two for a double check
+pub fn checking_pieces(game: &Gamestate) -> Vec<(u8, u8)> {
+    let color = Color::from_player(game.player);
+    let Some(king_square) = find_king(&game.board, color) else {
+        return Vec::new();
+    };
+
+    game.pieces_of(color.opposite())
+        .filter(|&(square, _)| attacked_squares(square, &game.board).contains(&king_square))
+        .map(|(square, _)| square)
+        .collect()
+}
+
+/// How many enemy pieces give check to the side to move: 0, 1, or 2 in a double check,
+/// when only king moves are legal
+pub fn check_count(game: &Gamestate) -> u8 {
+    checking_pieces(game).len() as u8
+}
+
+/// Checks whether capturing en passant from `from` onto `target` would leave the
+/// mover's king in check.
+///
+/// Both the capturing pawn and the captured pawn leave their rank, which can open a
+/// line onto the king that the ordinary pin logic never sees, so the capture is
+/// simulated on a copy of the board.
+pub fn en_passant_exposes_king(board: &[[char; 8]; 8], from: (u8, u8), target: (u8, u8)) -> bool {
+    let pawn = board[from.0 as usize][from.1 as usize];
+    let player = if pawn.is_uppercase() { 'w' } else { 'b' };
+
+    let mut temp_board = *board;
+    temp_board[target.0 as usize][target.1 as usize] = pawn;
+    temp_board[from.0 as usize][from.1 as usize] = ' ';
+    temp_board[from.0 as usize][target.1 as usize] = ' '; // the captured pawn
+
+    is_in_check(&temp_board, (0, 0), ('-', '-', '-', '-'), player)
+}
+
+/// Returns true when `current` is at least the third occurrence of its position: it
+/// appears at least twice among the earlier positions in `history`. Positions are
+/// compared by `Gamestate::position_key`, so the move clocks don't matter.
+pub fn is_threefold_repetition(history: &[Gamestate], current: &Gamestate) -> bool {
+    let key = current.position_key();
+    let earlier = history.iter().filter(|game| game.position_key() == key).count();
+    is_threefold_count(u8::try_from(earlier + 1).unwrap_or(u8::MAX))
+}
+
+/// Returns true when a position seen `count` times, counting the current one, may be
+/// claimed as a draw. The session's Zobrist counts are checked with this.
+pub fn is_threefold_count(count: u8) -> bool {
+    count >= 3
+}
+
+/// Returns true when a position seen `count` times is drawn without anyone claiming it
+pub fn is_fivefold_count(count: u8) -> bool {
+    count >= 5
 }
 
 /// Determines the game status for the current player
@@ -168,10 +583,16 @@ pub fn get_game_status(
     castling: (char, char, char, char),
     player: char,
 ) -> GameStatus {
-    let legal_moves = get_legal_moves(board, enpassat, castling, player);
+    let game = Gamestate {
+        board: *board,
+        player,
+        castling,
+        enpassat: (enpassat != (0, 0)).then_some(enpassat),
+        ..Gamestate::new()
+    };
     let in_check = is_in_check(board, enpassat, castling, player);
 
-    if legal_moves.is_empty() {
+    if !has_any_legal_move(&game) {
         if in_check {
             GameStatus::Checkmate
         } else {
@@ -188,33 +609,464 @@ pub fn get_game_status(
 fn is_square_attacked(
     pos: (u8, u8),
     board: &[[char; 8]; 8],
-    enpassat: (u8, u8),
+    _enpassat: (u8, u8),
     player: char,
 ) -> bool {
-    for rank in 0u8..8 {
-        for file in 0u8..8 {
-            let piece = board[rank as usize][file as usize];
-            if piece == ' ' || piece.is_uppercase() == (player == 'w') {
-                continue;
-            }
+    occupied_squares(board)
+        .filter(|&(_, piece)| piece.is_uppercase() != (player == 'w'))
+        .any(|(square, _)| attacked_squares(square, board).contains(&pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    /// King moves found the slow way: play every pseudo-legal king move (and, for
+    /// castling, the intermediate step) and test whether the king is attacked afterwards
+    fn king_moves_by_simulation(game: &Gamestate) -> Vec<CoordMove> {
+        let player = game.player;
+        let no_rights = ('-', '-', '-', '-');
+        let safe_after = |from: (u8, u8), to: (u8, u8)| {
+            let mut board = game.board;
+            board[to.0 as usize][to.1 as usize] = board[from.0 as usize][from.1 as usize];
+            board[from.0 as usize][from.1 as usize] = ' ';
+            !is_in_check(&board, (0, 0), no_rights, player)
+        };
 
-            let from = (rank, file);
-            let attack = match piece.to_ascii_lowercase() {
-                'p' => pawn::get_possible_moves(from, board, enpassat),
-                'r' => rook::get_possible_moves(from, board),
-                'n' => knight::get_possible_moves(from, board),
-                'b' => bishop::get_possible_moves(from, board),
-                'q' => queen::get_possible_moves(from, board),
-                'k' => king::get_possible_moves(from, board, (' ', ' ', ' ', ' ')),
-                _ => continue,
-            };
-
-            for direction in attack {
-                if direction.contains(&pos) {
-                    return true;
+        let mut moves = Vec::new();
+        for rank in 0u8..8 {
+            for file in 0u8..8 {
+                if game.board[rank as usize][file as usize] != 'K' {
+                    continue;
+                }
+                let from = (rank, file);
+                for to in king::get_possible_moves(from, &game.board, game.castling)
+                    .into_iter()
+                    .flatten()
+                {
+                    let legal = if (from.1 as i8 - to.1 as i8).abs() == 2 {
+                        let step = (from.0, (from.1 + to.1) / 2);
+                        !is_in_check(&game.board, (0, 0), no_rights, player)
+                            && safe_after(from, step)
+                            && safe_after(from, to)
+                    } else {
+                        safe_after(from, to)
+                    };
+                    if legal {
+                        moves.push((from, to));
+                    }
                 }
             }
         }
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn test_threefold_repetition() {
+        let shuffle = [
+            Move::new((7, 6), (5, 5)), // Ng1-f3
+            Move::new((0, 6), (2, 5)), // Ng8-f6
+            Move::new((5, 5), (7, 6)), // Nf3-g1
+            Move::new((2, 5), (0, 6)), // Nf6-g8
+        ];
+
+        let mut game = Gamestate::starting_position();
+        let mut history = Vec::new();
+        let mut repeated = Vec::new();
+        for mv in shuffle.iter().cycle().take(8) {
+            history.push(game.clone());
+            crate::validation::make_move::apply_move(&mut game, mv);
+            repeated.push(is_threefold_repetition(&history, &game));
+        }
+
+        // Only the start position, reached again after each shuffle, comes up three times
+        assert_eq!(repeated, [false, false, false, false, false, false, false, true]);
+        assert_ne!(game.halfmove, history[0].halfmove);
+        assert!(!is_threefold_repetition(&[], &game));
+    }
+
+    #[test]
+    fn test_repetition_counts() {
+        assert!(!is_threefold_count(2));
+        assert!(is_threefold_count(3));
+        assert!(!is_fivefold_count(4));
+        assert!(is_fivefold_count(5));
+    }
+
+    #[test]
+    fn test_shredder_castling_field_castles() {
+        let moves = |fen: &str| {
+            let game = Gamestate::from_str(fen).unwrap();
+            let mut moves = get_legal_moves(
+                &game.board,
+                game.enpassat.unwrap_or((0, 0)),
+                game.castling,
+                game.player,
+            );
+            moves.sort();
+            moves
+        };
+        let shredder = moves("r3k2r/8/8/8/8/8/8/R3K2R w HAha - 0 1");
+        assert_eq!(shredder, moves("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"));
+        assert!(shredder.contains(&((7, 4), (7, 6))));
+        assert!(shredder.contains(&((7, 4), (7, 2))));
+    }
+
+    #[test]
+    fn test_get_legal_moves_no_castling_through_f1() {
+        let castles = |fen: &str| {
+            let game = Gamestate::from_str(fen).unwrap();
+            get_legal_moves(&game.board, (0, 0), game.castling, game.player)
+                .contains(&((7, 4), (7, 6)))
+        };
+        assert!(castles("4k3/8/8/8/8/8/8/4K2R w K - 0 1"));
+        // The f8 rook, the c4 bishop and the g2 pawn each guard f1
+        assert!(!castles("4kr2/8/8/8/8/8/8/4K2R w K - 0 1"));
+        assert!(!castles("4k3/8/8/8/2b5/8/8/4K2R w K - 0 1"));
+        assert!(!castles("4k3/8/8/8/8/8/6p1/4K2R w K - 0 1"));
+    }
+
+    #[test]
+    fn test_attack_table_king_filter_matches_simulation() {
+        let positions = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            // King in check from a rook: stepping back along the file is still attacked
+            "4r1k1/8/8/8/8/8/8/4K3 w - - 0 1",
+            // Defended pieces next to the king, castling through an attacked square
+            "4k3/8/8/8/8/5q2/3pP3/R3K2R w KQ - 0 1",
+            // Pawn diagonals guard f1 and d1 without any pawn push squares counting
+            "4k3/8/8/8/8/8/3p1p2/R3K2R w KQ - 0 1",
+        ];
+        for fen in positions {
+            let game = Gamestate::from_str(fen).unwrap();
+            let enpassat = game.enpassat.unwrap_or((0, 0));
+            let mut table_moves: Vec<CoordMove> =
+                get_legal_moves(&game.board, enpassat, game.castling, game.player)
+                    .into_iter()
+                    .filter(|&(from, _)| game.board[from.0 as usize][from.1 as usize] == 'K')
+                    .collect();
+            table_moves.sort();
+            assert_eq!(table_moves, king_moves_by_simulation(&game), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_mirrored_positions_have_mirrored_moves() {
+        let positions = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+        let moves_of = |game: &Gamestate| {
+            get_legal_moves(
+                &game.board,
+                game.enpassat.unwrap_or((0, 0)),
+                game.castling,
+                game.player,
+            )
+        };
+        for fen in positions {
+            let game = Gamestate::from_str(fen).unwrap();
+            let mut expected: Vec<CoordMove> = moves_of(&game)
+                .into_iter()
+                .map(|(from, to)| ((7 - from.0, from.1), (7 - to.0, to.1)))
+                .collect();
+            let mut mirrored = moves_of(&game.mirror());
+            expected.sort();
+            mirrored.sort();
+            assert_eq!(mirrored, expected, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_legal_moves_expands_promotions() {
+        // The white king and the a2 pawn are stuck, leaving only the e7 pawn to move
+        let game = Gamestate::from_str("8/4P3/8/8/8/p7/P7/K1k5 w - - 0 1").unwrap();
+        let moves = legal_moves(&game);
+        assert_eq!(moves.len(), 4);
+        assert!(moves.iter().all(|m| m.from == (1, 4) && m.to == (0, 4)));
+        assert!(moves.contains(&Move::with_promotion((1, 4), (0, 4), 'n')));
+    }
+
+    #[test]
+    fn test_legal_moves_from_pinned_knight() {
+        // The knight on e2 is pinned to its king by the rook on e8
+        let game = Gamestate::from_str("4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+        assert!(legal_moves_from(&game, (6, 4)).is_empty());
+
+        let mut king_moves = legal_moves_from(&game, (7, 4));
+        king_moves.sort();
+        assert_eq!(king_moves, vec![(6, 3), (6, 5), (7, 3), (7, 5)]);
+        assert!(legal_moves_from(&game, (4, 4)).is_empty()); // Empty square
+    }
+
+    #[test]
+    fn test_get_legal_moves_keeps_pins() {
+        let moves_from = |fen: &str, from: (u8, u8)| -> Vec<(u8, u8)> {
+            let game = Gamestate::from_str(fen).unwrap();
+            let mut moves: Vec<_> = get_legal_moves(
+                &game.board,
+                game.enpassat.unwrap_or((0, 0)),
+                game.castling,
+                game.player,
+            )
+            .into_iter()
+            .filter(|&(square, _)| square == from)
+            .map(|(_, to)| to)
+            .collect();
+            moves.sort();
+            moves
+        };
+
+        // The e2 knight is pinned by the e8 rook, while a knight off the king's lines is free
+        assert!(moves_from("k3r3/8/8/8/8/8/4N3/4K3 w - - 0 1", (6, 4)).is_empty());
+        assert_eq!(moves_from("k7/8/8/8/8/2N5/8/4K3 w - - 0 1", (5, 2)).len(), 8);
+
+        // The d2 pawn is pinned by the b4 bishop, but may take it from c3
+        assert!(moves_from("k7/8/8/8/1b6/8/3P4/4K3 w - - 0 1", (6, 3)).is_empty());
+        assert_eq!(moves_from("k7/8/8/8/8/2b5/3P4/4K3 w - - 0 1", (6, 3)), vec![(5, 2)]);
+    }
+
+    #[test]
+    fn test_checking_destinations_from() {
+        // The queen on c3 checks from c8 and h8; the pawns cover the rest of the king
+        let game = Gamestate::from_str("4k3/3ppp2/8/8/8/2Q5/8/7K w - - 0 1").unwrap();
+        let mut checks = checking_destinations_from(&game, (5, 2));
+        checks.sort();
+        assert_eq!(checks, vec![(0, 2), (0, 7)]);
+        assert!(checking_destinations_from(&game, (7, 7)).is_empty());
+    }
+
+    #[test]
+    fn test_legal_moves_for_piece() {
+        let game = Gamestate::starting_position();
+        let mut knight_moves: Vec<_> = legal_moves_for_piece(&game, Piece::Knight)
+            .into_iter()
+            .map(|mv| mv.to_string())
+            .collect();
+        knight_moves.sort();
+        assert_eq!(knight_moves, vec!["b1-a3", "b1-c3", "g1-f3", "g1-h3"]);
+        assert_eq!(legal_moves_for_piece(&game, Piece::Pawn).len(), 16);
+        assert!(legal_moves_for_piece(&game, Piece::Queen).is_empty());
+    }
+
+    #[test]
+    fn test_legal_moves_to_two_knights() {
+        // Knights on c3 and g3 can both reach e4; nothing else can
+        let game = Gamestate::from_str("4k3/8/8/8/8/2N3N1/8/4K3 w - - 0 1").unwrap();
+        let mut sources = legal_moves_to(&game, (4, 4));
+        sources.sort();
+        assert_eq!(sources, vec![(5, 2), (5, 6)]);
+        assert!(legal_moves_to(&game, (0, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_leaves_king_in_check() {
+        // The bishop on d2 is pinned against the king by the bishop on a5
+        let game = Gamestate::from_str("4k3/8/8/b7/8/8/3B4/4K3 w - - 0 1").unwrap();
+        assert!(leaves_king_in_check(&game, (6, 3), (5, 4)));
+        assert!(!leaves_king_in_check(&game, (6, 3), (5, 2)));
+        assert!(!leaves_king_in_check(&game, (6, 3), (3, 0))); // Capturing the pinner
+
+        // En passant that removes both pawns from the king's rank
+        let game = Gamestate::from_str("8/8/8/K2pP2r/8/8/8/7k w - d6 0 1").unwrap();
+        assert!(leaves_king_in_check(&game, (3, 4), (2, 3)));
+    }
+
+    #[test]
+    fn test_has_any_legal_move() {
+        // Fool's mate
+        let mated =
+            Gamestate::from_str("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3")
+                .unwrap();
+        assert!(!has_any_legal_move(&mated));
+        assert!(has_any_legal_move(&Gamestate::starting_position()));
+
+        // Stalemate: the black king has no moves and is not in check
+        let stalemate = Gamestate::from_str("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(!has_any_legal_move(&stalemate));
+    }
+
+    #[test]
+    fn test_en_passant_resolves_pawn_check() {
+        // The d4 pawn checks the king on c5 and can only be removed en passant
+        let game = Gamestate::from_str("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1").unwrap();
+        let moves = get_legal_moves(&game.board, (5, 3), game.castling, game.player);
+        assert!(moves.contains(&((4, 4), (5, 3))));
+        assert!(has_any_legal_move(&game));
+    }
+
+    #[test]
+    fn test_en_passant_along_the_king_rank() {
+        // dxe3 would take both pawns off the fourth rank and open it to the h4 rook
+        let game = Gamestate::from_str("8/8/8/8/k2pP2R/8/8/K7 b - e3 0 1").unwrap();
+        let moves = get_legal_moves(&game.board, (5, 4), game.castling, game.player);
+        assert!(!moves.contains(&((4, 3), (5, 4))));
+        assert!(moves.contains(&((4, 3), (5, 3)))); // After d3 the e4 pawn still blocks the rook
+
+        // Without the rook the capture is fine
+        let game = Gamestate::from_str("8/8/8/8/k2pP3/8/8/K7 b - e3 0 1").unwrap();
+        let moves = get_legal_moves(&game.board, (5, 4), game.castling, game.player);
+        assert!(moves.contains(&((4, 3), (5, 4))));
+    }
+
+    #[test]
+    fn test_checking_pieces() {
+        // Double check from the knight on f6 and the rook on e1
+        let game = Gamestate::from_str("4k3/8/5N2/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+        let mut checkers = checking_pieces(&game);
+        checkers.sort();
+        assert_eq!(checkers, vec![(2, 5), (7, 4)]);
+
+        // A single check, and none at the start
+        let game = Gamestate::from_str("4k3/8/8/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+        assert_eq!(checking_pieces(&game), vec![(7, 4)]);
+        assert!(checking_pieces(&Gamestate::starting_position()).is_empty());
+    }
+
+    #[test]
+    fn test_check_count() {
+        // The bishop on b5 and the rook on e1 both check the king on e8
+        let game = Gamestate::from_str("4k3/8/8/1B6/8/8/8/4R1K1 b - - 0 1").unwrap();
+        assert_eq!(check_count(&game), 2);
+        assert!(legal_moves(&game).iter().all(|mv| mv.from == (0, 4)));
+
+        let game = Gamestate::from_str("4k3/8/8/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+        assert_eq!(check_count(&game), 1);
+        assert_eq!(check_count(&Gamestate::starting_position()), 0);
+    }
+
+    #[test]
+    fn test_legal_promotions_push() {
+        let game = Gamestate::from_str("8/4P3/8/8/8/8/8/k6K w - - 0 1").unwrap();
+        let promotions = legal_promotions(&game, (1, 4));
+        assert_eq!(promotions.len(), 4);
+        for piece in PROMOTION_PIECES {
+            assert!(promotions.contains(&Move::with_promotion((1, 4), (0, 4), piece)));
+        }
+    }
+
+    #[test]
+    fn test_legal_promotions_capture() {
+        // e8 is free and the rook on d8 can be captured
+        let game = Gamestate::from_str("3r4/4P3/8/8/8/8/8/k6K w - - 0 1").unwrap();
+        assert_eq!(legal_promotions(&game, (1, 4)).len(), 8);
+    }
+
+    #[test]
+    fn test_legal_promotions_pinned() {
+        // The pawn is pinned against its king by the rook on a7
+        let game = Gamestate::from_str("8/r3P2K/8/8/8/8/8/k7 w - - 0 1").unwrap();
+        assert!(legal_promotions(&game, (1, 4)).is_empty());
+    }
+
+    #[test]
+    fn test_castling_denial_reasons() {
+        let legality = |fen: &str, kingside: bool| {
+            castling_legality(&Gamestate::from_str(fen).unwrap(), Color::White, kingside)
+        };
+
+        assert_eq!(legality("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", true), Ok(()));
+        assert_eq!(legality("4k3/8/8/8/8/8/8/R3K2R w Q - 0 1", true), Err(CastleDenial::NoRight));
+        // The right is still listed but the rook has gone
+        assert_eq!(legality("4k3/8/8/8/8/8/8/R3K3 w KQ - 0 1", true), Err(CastleDenial::NoRight));
+        assert_eq!(
+            legality("4k3/8/8/8/8/8/8/RN2K2R w KQ - 0 1", false),
+            Err(CastleDenial::PathBlocked)
+        );
+        assert_eq!(
+            legality("4r1k1/8/8/8/8/8/8/R3K2R w KQ - 0 1", true),
+            Err(CastleDenial::InCheck)
+        );
+        assert_eq!(
+            legality("5rk1/8/8/8/8/8/8/R3K2R w KQ - 0 1", true),
+            Err(CastleDenial::PassesThroughCheck)
+        );
+        assert_eq!(
+            legality("2r3k1/8/8/8/8/8/8/R3K2R w KQ - 0 1", false),
+            Err(CastleDenial::EndsInCheck)
+        );
+        // Only the b-file square may be attacked on the queenside
+        assert_eq!(legality("1r4k1/8/8/8/8/8/8/R3K2R w KQ - 0 1", false), Ok(()));
+    }
+
+    #[test]
+    fn test_capture_moves_en_passant() {
+        // d6 is the en passant square: e5xd6 captures the d5 pawn
+        let game = Gamestate::from_str("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        assert_eq!(capture_moves(&game), vec![Move::new((3, 4), (2, 3))]);
+
+        // Same board without the en passant square: the diagonal step is no move at all
+        let game = Gamestate::from_str("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 2").unwrap();
+        assert!(capture_moves(&game).is_empty());
+        assert!(!legal_moves_from(&game, (3, 4)).contains(&(2, 3)));
+
+        // Taking en passant would open the fifth rank onto the king
+        let game = Gamestate::from_str("8/8/8/K2pP2r/8/8/8/7k w - d6 0 1").unwrap();
+        assert!(capture_moves(&game).is_empty());
+    }
+
+    #[test]
+    fn test_no_phantom_en_passant_onto_a8() {
+        // With no en passant square, a pawn on b7 must not "capture" onto empty a8
+        let game = Gamestate::from_str("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(legal_moves_from(&game, (1, 1)), vec![(0, 1)]);
+        assert!(capture_moves(&game).is_empty());
+    }
+
+    #[test]
+    fn test_explain_illegal() {
+        let explain = |fen: &str, from, to| {
+            explain_illegal(&Gamestate::from_str(fen).unwrap(), from, to, None)
+        };
+
+        // The e2 knight is pinned by the e8 rook
+        let pinned = "4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1";
+        assert_eq!(
+            explain(pinned, (6, 4), (4, 3)).as_deref(),
+            Some("the move would leave the king in check")
+        );
+        assert_eq!(explain(pinned, (5, 4), (4, 4)).as_deref(), Some("there is no piece on e3"));
+        assert_eq!(
+            explain(pinned, (0, 6), (0, 7)).as_deref(),
+            Some("not your turn: the piece on g8 is black and white is to move")
+        );
+        assert_eq!(explain(pinned, (7, 4), (7, 3)), None);
+
+        let castling = "4k3/8/8/8/8/8/8/R3K1NR w KQ - 0 1";
+        assert_eq!(
+            explain(castling, (7, 4), (7, 6)).as_deref(),
+            Some("castling path is blocked")
+        );
+        assert_eq!(explain(castling, (7, 4), (7, 2)), None);
+    }
+
+    #[test]
+    fn test_legal_moves_map() {
+        let map = legal_moves_map(&Gamestate::starting_position());
+        assert_eq!(map.len(), 10);
+        for file in 0..8 {
+            assert_eq!(map[&(6, file)].len(), 2);
+        }
+        assert_eq!(map[&(7, 6)], vec![(5, 5), (5, 7)]);
+        assert_eq!(map[&(7, 1)].len(), 2);
+    }
+
+    #[test]
+    fn test_is_quiet() {
+        assert!(is_quiet(&Gamestate::starting_position()));
+
+        // The black knight on d5 hangs to the e4 pawn
+        let game = Gamestate::from_str("4k3/8/8/3n4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        assert!(!is_quiet(&game));
+
+        // In check from the rook, with nothing to capture
+        let game = Gamestate::from_str("4k3/8/8/8/8/8/8/r3K3 w - - 0 1").unwrap();
+        assert!(capture_moves(&game).is_empty());
+        assert!(!is_quiet(&game));
     }
-    false
 }
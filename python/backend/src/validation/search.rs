@@ -0,0 +1,214 @@
+/*
+ * Author: Renier Barnard
+ * Searching the move tree for tactics and best moves
+ */
+
+use super::draw::{material_verdict, MaterialVerdict};
+use super::evaluation::Evaluation;
+use super::exchange::static_exchange;
+use super::make_move::{apply_move, undo_move};
+use super::pieces::Piece;
+use super::possible_moves::{capture_moves, get_game_status, legal_moves, GameStatus};
+use crate::parsers::{fen_parser::Gamestate, notation::Move};
+use std::cmp::Reverse;
+
+/// Score of being checkmated at the root. Mates further down the tree score slightly
+/// less, so the search prefers the quickest mate and the slowest loss.
+pub const MATE_SCORE: i32 = 100_000;
+
+/// Bound on every score, above any mate score
+const INFINITY: i32 = MATE_SCORE + 1;
+
+/// Returns every legal move that checkmates the opponent straight away
+pub fn find_mate_in_one(game: &Gamestate) -> Vec<Move> {
+    let mut scratch = game.clone();
+    legal_moves(game)
+        .into_iter()
+        .filter(|mv| {
+            let undo = apply_move(&mut scratch, mv);
+            let status = get_game_status(
+                &scratch.board,
+                scratch.enpassat.unwrap_or((0, 0)),
+                scratch.castling,
+                scratch.player,
+            );
+            undo_move(&mut scratch, mv, undo);
+            matches!(status, GameStatus::Checkmate)
+        })
+        .collect()
+}
+
+/// Finds the best move for the side to move with a `depth`-ply alpha-beta search over
+/// the material evaluation. See `search_with`.
+pub fn search(game: &Gamestate, depth: u32) -> Option<(Move, i32)> {
+    search_with(game, depth, Evaluation::Material)
+}
+
+/// Negamax search with alpha-beta pruning, `depth` plies deep (at least 1), scoring
+/// leaves with `evaluation`.
+///
+/// Returns the best move and its score in centipawns from the point of view of the side
+/// to move: positive is good for the mover. Checkmate scores close to `MATE_SCORE`;
+/// stalemate, the 50-move rule and dead positions score 0. Returns `None` when there
+/// is no legal move.
+pub fn search_with(game: &Gamestate, depth: u32, evaluation: Evaluation) -> Option<(Move, i32)> {
+    let mut scratch = game.clone();
+    let mut best: Option<(Move, i32)> = None;
+    let mut alpha = -INFINITY;
+
+    for mv in ordered_moves(game) {
+        let undo = apply_move(&mut scratch, &mv);
+        let score = -negamax(&mut scratch, depth.max(1) - 1, 1, -INFINITY, -alpha, evaluation);
+        undo_move(&mut scratch, &mv, undo);
+
+        if score > alpha {
+            alpha = score;
+            best = Some((mv, score));
+        }
+    }
+    best
+}
+
+fn negamax(
+    game: &mut Gamestate,
+    depth: u32,
+    ply: i32,
+    mut alpha: i32,
+    beta: i32,
+    evaluation: Evaluation,
+) -> i32 {
+    if game.halfmove >= 100 || material_verdict(&game.board) == MaterialVerdict::DeadPosition {
+        return 0;
+    }
+
+    let moves = ordered_moves(game);
+    if moves.is_empty() {
+        let status = get_game_status(
+            &game.board,
+            game.enpassat.unwrap_or((0, 0)),
+            game.castling,
+            game.player,
+        );
+        return match status {
+            GameStatus::Checkmate => -(MATE_SCORE - ply),
+            _ => 0,
+        };
+    }
+
+    if depth == 0 {
+        return resolve_captures(game, evaluation);
+    }
+
+    for mv in moves {
+        let undo = apply_move(game, &mv);
+        let score = -negamax(game, depth - 1, ply + 1, -beta, -alpha, evaluation);
+        undo_move(game, &mv, undo);
+
+        if score >= beta {
+            return beta;
+        }
+        alpha = alpha.max(score);
+    }
+    alpha
+}
+
+/// Static eval for the side to move
+fn static_score(game: &Gamestate, evaluation: Evaluation) -> i32 {
+    let score = evaluation.evaluate(&game.board);
+    if game.player == 'w' {
+        score
+    } else {
+        -score
+    }
+}
+
+/// Leaf score with a one-ply capture extension: if the side to move has a capture that
+/// wins material by static exchange, the better of standing pat and the best such
+/// capture is returned. This keeps the search from stopping just before a piece it left
+/// hanging is taken.
+fn resolve_captures(game: &mut Gamestate, evaluation: Evaluation) -> i32 {
+    let mut best = static_score(game, evaluation);
+    for mv in capture_moves(game) {
+        if static_exchange(game, &mv) <= 0 {
+            continue;
+        }
+        let undo = apply_move(game, &mv);
+        best = best.max(-static_score(game, evaluation));
+        undo_move(game, &mv, undo);
+    }
+    best
+}
+
+/// Legal moves with the most valuable captures and promotions first, so alpha-beta
+/// finds cutoffs early
+fn ordered_moves(game: &Gamestate) -> Vec<Move> {
+    let mut moves = legal_moves(game);
+    moves.sort_by_key(|mv| {
+        let victim = game.board[mv.to.0 as usize][mv.to.1 as usize];
+        let captured = Piece::from_char(victim).map_or(0, Piece::value);
+        let promoted = mv.promotion.and_then(Piece::from_char).map_or(0, Piece::value);
+        Reverse(captured + promoted)
+    });
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_back_rank_mate_in_one() {
+        let game = Gamestate::from_str("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(find_mate_in_one(&game), vec![Move::new((7, 0), (0, 0))]);
+    }
+
+    #[test]
+    fn test_no_mate_in_one() {
+        assert!(find_mate_in_one(&Gamestate::starting_position()).is_empty());
+
+        // The h7 pawn gives the king an escape square
+        let game = Gamestate::from_str("6k1/5pp1/7p/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert!(find_mate_in_one(&game).is_empty());
+    }
+
+    #[test]
+    fn test_search_avoids_hanging_the_queen() {
+        // Qxd5 wins a pawn but loses the queen to exd5
+        let game = Gamestate::from_str("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let queen_takes = Move::new((7, 3), (3, 3));
+
+        let (best, score) = search(&game, 2).unwrap();
+        assert_ne!(best, queen_takes);
+        assert!(score > -200, "score {}", score);
+    }
+
+    #[test]
+    fn test_capture_extension_sees_the_recapture() {
+        // One ply deep, Qxd5 looks a pawn up on the static eval alone (+800 against +700
+        // for quiet moves). At the leaf Black has the winning capture exd5, so the
+        // extension scores it as the queen lost.
+        let game = Gamestate::from_str("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let queen_takes = Move::new((7, 3), (3, 3));
+
+        let mut after = game.clone();
+        apply_move(&mut after, &queen_takes);
+        assert_eq!(-static_score(&after, Evaluation::Material), 800);
+        assert_eq!(-resolve_captures(&mut after, Evaluation::Material), -100);
+
+        let (best, _) = search(&game, 1).unwrap();
+        assert_ne!(best, queen_takes);
+    }
+
+    #[test]
+    fn test_search_scores_mate_and_stalemate() {
+        let game = Gamestate::from_str("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let (best, score) = search(&game, 2).unwrap();
+        assert_eq!(best, Move::new((7, 0), (0, 0)));
+        assert_eq!(score, MATE_SCORE - 1);
+
+        // Black to move has no legal moves at all
+        let stalemate = Gamestate::from_str("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(search(&stalemate, 2), None);
+    }
+}
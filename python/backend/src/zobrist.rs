@@ -0,0 +1,108 @@
+/*
+ * Author: Renier Barnard
+ * Zobrist hashing of positions
+ *
+ * Every (piece, square) pair, castling right, en passant file and the side to move
+ * gets a fixed random key; a position's hash is the XOR of the keys that apply to it.
+ * The keys are generated at compile time from a fixed seed, so hashes are stable
+ * across runs and builds.
+ */
+
+use crate::parsers::fen_parser::Gamestate;
+
+const PIECES: [char; 12] = ['P', 'N', 'B', 'R', 'Q', 'K', 'p', 'n', 'b', 'r', 'q', 'k'];
+
+/// 12 pieces × 64 squares, then 4 castling rights, 8 en passant files and black to move
+const KEY_COUNT: usize = 12 * 64 + 4 + 8 + 1;
+const CASTLING_KEYS: usize = 12 * 64;
+const EN_PASSANT_KEYS: usize = CASTLING_KEYS + 4;
+const BLACK_TO_MOVE_KEY: usize = EN_PASSANT_KEYS + 8;
+
+const KEYS: [u64; KEY_COUNT] = generate_keys(0x9E37_79B9_7F4A_7C15);
+
+/// Fills the key table with the SplitMix64 sequence starting at `seed`
+const fn generate_keys(seed: u64) -> [u64; KEY_COUNT] {
+    let mut keys = [0u64; KEY_COUNT];
+    let mut state = seed;
+    let mut i = 0;
+    while i < KEY_COUNT {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+}
+
+fn piece_key(piece: char, rank: usize, file: usize) -> Option<u64> {
+    let index = PIECES.iter().position(|&p| p == piece)?;
+    Some(KEYS[index * 64 + rank * 8 + file])
+}
+
+/// Returns the Zobrist hash of the board, side to move, castling rights and en
+/// passant square. The move clocks are not part of the hash, and neither is an en
+/// passant square no pawn can legally take on, the same rule `to_fen_strict` uses.
+pub fn hash(game: &Gamestate) -> u64 {
+    let mut hash = 0u64;
+
+    for (rank, row) in game.board.iter().enumerate() {
+        for (file, &piece) in row.iter().enumerate() {
+            if let Some(key) = piece_key(piece, rank, file) {
+                hash ^= key;
+            }
+        }
+    }
+
+    let (k, q, bk, bq) = game.castling;
+    for (i, right) in [k, q, bk, bq].into_iter().enumerate() {
+        if right != '-' {
+            hash ^= KEYS[CASTLING_KEYS + i];
+        }
+    }
+
+    if let Some((_, file)) = game.enpassat {
+        if !game.en_passant_capturers().is_empty() {
+            hash ^= KEYS[EN_PASSANT_KEYS + (file as usize % 8)];
+        }
+    }
+
+    if game.player == 'b' {
+        hash ^= KEYS[BLACK_TO_MOVE_KEY];
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_hash_ignores_clocks_only() {
+        let game = Gamestate::from_str("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        let later = Gamestate::from_str("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 7 30").unwrap();
+        assert_eq!(hash(&game), hash(&later));
+
+        let variants = [
+            "r3k2r/8/8/3pP3/8/8/8/R3K2R b KQkq d6 0 1",
+            "r3k2r/8/8/3pP3/8/8/8/R3K2R w Kkq d6 0 1",
+            "r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq - 0 1",
+            "r3k2r/8/8/3p1P2/8/8/8/R3K2R w KQkq - 0 1",
+        ];
+        for fen in variants {
+            let other = Gamestate::from_str(fen).unwrap();
+            assert_ne!(hash(&game), hash(&other), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_hash_skips_untakeable_en_passant() {
+        // After 1. e4 no black pawn stands next to e4, so e3 changes nothing
+        let with = Gamestate::from_str("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").unwrap();
+        let without = Gamestate::from_str("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(hash(&with), hash(&without));
+    }
+}